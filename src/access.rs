@@ -0,0 +1,258 @@
+use crate::*;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+const INVITE_TOKEN_LEN: usize = 16;
+const REMOVE_MEMBER_ARGS: [&str; 2] = ["удалить", "remove"];
+
+// Права проверяются здесь, до вызова обработчиков: наблюдателю доступны только отчёты
+pub fn required_role(command: &Command) -> Role {
+    match command {
+        Command::ClearAllExpenses | Command::DeleteCategory | Command::Invite(_) | Command::Role(_) => Role::Owner,
+        Command::Start(_)
+        | Command::Help
+        | Command::AllExpenses(_)
+        | Command::TotalExpenses(_)
+        | Command::ExpensesByCategory(_)
+        | Command::ExpensesByTag(_)
+        | Command::Attachment(_)
+        | Command::Incomes(_)
+        | Command::Balance(_)
+        | Command::Accounts
+        | Command::Recurring
+        | Command::Budgets
+        | Command::Goals
+        | Command::Summary(_)
+        | Command::Export(_)
+        | Command::Rules
+        | Command::Ledger(_)
+        | Command::ByMember(_)
+        | Command::Settle => Role::Viewer,
+        _ => Role::Editor,
+    }
+}
+
+// Личный бюджет принадлежит владельцу чата, в общих роль хранится у участника.
+// Новичок группового чата до первого сообщения считается редактором, как и после.
+// None - участника удалили из бюджета, и доступа у него нет совсем
+pub fn ledger_role(user_data: &HashMap<ChatId, UserData>, chat_id: ChatId, user_id: UserId) -> Option<Role> {
+    let ledger_id = ledger_id(user_data, chat_id);
+    if ledger_id == ChatId::from(user_id) {
+        return Some(Role::Owner);
+    }
+
+    let ledger = user_data.get(&ledger_id);
+    if ledger.is_some_and(|ledger| ledger.removed.contains(&user_id)) {
+        return None;
+    }
+    let role = ledger
+        .and_then(|ledger| ledger.members.iter().find(|member| member.id == user_id))
+        .map(|member| member.role)
+        .unwrap_or_default();
+    Some(role)
+}
+
+pub async fn is_command_forbidden(msg: Message, command: Command, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) -> bool {
+    let Some(user) = msg.from.as_ref() else {
+        return true;
+    };
+    let data = user_data.lock().await;
+    ledger_role(&data, msg.chat.id, user.id) < Some(required_role(&command))
+}
+
+// Всё, что не команда, - это запись трат и диалоги, для них нужны права редактора
+pub async fn is_message_forbidden(msg: Message, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) -> bool {
+    let Some(user) = msg.from.as_ref() else {
+        return true;
+    };
+    let data = user_data.lock().await;
+    ledger_role(&data, msg.chat.id, user.id) < Some(Role::Editor)
+}
+
+pub async fn is_callback_forbidden(q: CallbackQuery, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) -> bool {
+    let read_only = [BY_CATEGORY_CALLBACK_PREFIX, EXPORT_CALLBACK_PREFIX].iter()
+        .any(|prefix| is_callback_with_prefix(&q, prefix));
+    let required = if read_only { Role::Viewer } else { Role::Editor };
+
+    let data = user_data.lock().await;
+    ledger_role(&data, callback_chat_id(&q), q.from.id) < Some(required)
+}
+
+pub async fn send_forbidden(bot: Bot, msg: Message) -> HandlerResult {
+    info!("Command forbidden for user {:?}", msg.from.as_ref().map(|user| user.id));
+    bot.send_message(msg.chat.id, "Недостаточно прав для этой команды в этом бюджете").await?;
+    Ok(())
+}
+
+pub async fn send_read_only_notice(bot: Bot, msg: Message) -> HandlerResult {
+    if msg.chat.is_private() {
+        bot.send_message(msg.chat.id, "У вас доступ только на просмотр этого бюджета. Вернуться к личному: /ledger личный").await?;
+    }
+    Ok(())
+}
+
+pub async fn answer_forbidden_callback(bot: Bot, q: CallbackQuery) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).text("Недостаточно прав").await?;
+    Ok(())
+}
+
+pub async fn handle_invite_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    me: Me,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /invite");
+    let role = if args.trim().is_empty() { Some(Role::Editor) } else { parse_role(args.trim()) };
+    let Some(role) = role.filter(|role| *role != Role::Owner) else {
+        bot.send_message(msg.chat.id, "Укажите роль: /invite редактор или /invite наблюдатель").await?;
+        return Ok(());
+    };
+
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(INVITE_TOKEN_LEN)
+        .map(char::from)
+        .collect();
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    if user_entry.title.is_none() {
+        let owner = msg.from.as_ref().map(|user| user.full_name()).unwrap_or_default();
+        user_entry.title = Some(format!("Бюджет {}", owner));
+    }
+    user_entry.invites.push(Invite { token: token.clone(), role });
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!(
+        "Ссылка-приглашение ({}), действует один раз:\nhttps://t.me/{}?start={}",
+        role_name(role),
+        me.username(),
+        token
+    )).await?;
+    Ok(())
+}
+
+pub async fn handle_role_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /role");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let [index, role] = words.as_slice() else {
+        bot.send_message(msg.chat.id, "Укажите номер участника из /ledger и роль, например: /role 2 наблюдатель или /role 2 удалить").await?;
+        return Ok(());
+    };
+    let remove = REMOVE_MEMBER_ARGS.contains(&role.to_lowercase().as_str());
+    let role = parse_role(role);
+    if role.is_none() && !remove {
+        bot.send_message(msg.chat.id, "Роли: владелец, редактор, наблюдатель").await?;
+        return Ok(());
+    }
+
+    let user_id = msg.from.as_ref().unwrap().id;
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let Some(member) = index.parse::<usize>().ok().and_then(|index| user_entry.members.get_mut(index)) else {
+        bot.send_message(msg.chat.id, "Нет участника с таким номером, список: /ledger").await?;
+        return Ok(());
+    };
+    if member.id == user_id {
+        bot.send_message(msg.chat.id, "Нельзя изменить собственную роль").await?;
+        return Ok(());
+    }
+
+    let message = match role {
+        Some(role) if !remove => {
+            member.role = role;
+            format!("{} теперь {}", member.name, role_name(role))
+        }
+        _ => {
+            let member_id = member.id;
+            let message = format!("{} больше не участвует в бюджете", member.name);
+            user_entry.members.retain(|member| member.id != member_id);
+            if !user_entry.removed.contains(&member_id) {
+                user_entry.removed.push(member_id);
+            }
+            message
+        }
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+// Приглашение одноразовое: после использования токен удаляется
+pub async fn accept_invite(
+    bot: &Bot,
+    msg: &Message,
+    token: &str,
+    user_data: &Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Accepting invite");
+    let Some(user) = msg.from.as_ref().filter(|_| msg.chat.is_private()) else {
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let found = data.iter_mut()
+        .find_map(|(id, ledger)| {
+            let position = ledger.invites.iter().position(|invite| invite.token == token)?;
+            Some((*id, ledger.invites.remove(position).role))
+        });
+    let Some((ledger_id, role)) = found else {
+        bot.send_message(msg.chat.id, "Приглашение недействительно или уже использовано").await?;
+        return Ok(());
+    };
+    if ledger_id == msg.chat.id {
+        bot.send_message(msg.chat.id, "Это приглашение в ваш собственный бюджет").await?;
+        return Ok(());
+    }
+
+    let ledger = data.get_mut(&ledger_id).unwrap();
+    ledger.removed.retain(|id| *id != user.id);
+    ledger.members.retain(|member| member.id != user.id);
+    ledger.members.push(LedgerMember { id: user.id, name: user.full_name(), role });
+    let title = ledger.title.clone().unwrap_or_else(|| ledger_id.to_string());
+    data.entry(msg.chat.id).or_default().linked_ledger = Some(ledger_id);
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!(
+        "Вы присоединились к бюджету '{}' ({}). Вернуться к личному бюджету: /ledger личный",
+        title,
+        role_name(role)
+    )).await?;
+    Ok(())
+}
+
+pub fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Owner => "владелец",
+        Role::Editor => "редактор",
+        Role::Viewer => "наблюдатель",
+    }
+}
+
+fn parse_role(word: &str) -> Option<Role> {
+    match word.to_lowercase().as_str() {
+        "владелец" | "owner" => Some(Role::Owner),
+        "редактор" | "editor" => Some(Role::Editor),
+        "наблюдатель" | "viewer" => Some(Role::Viewer),
+        _ => None,
+    }
+}
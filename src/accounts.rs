@@ -0,0 +1,196 @@
+use crate::*;
+
+pub const ACCOUNT_PREFIX: char = '@';
+
+pub async fn handle_add_account_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /addaccount");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (name, initial_balance) = match words.as_slice() {
+        [name] => (name.trim_start_matches(ACCOUNT_PREFIX), Some(0.0)),
+        [name, balance] => (name.trim_start_matches(ACCOUNT_PREFIX), balance.parse::<f64>().ok().filter(|balance| balance.is_finite())),
+        _ => ("", None),
+    };
+
+    let Some(initial_balance) = initial_balance.filter(|_| !name.is_empty()) else {
+        bot.send_message(msg.chat.id,
+            "Укажите название счёта одним словом и, при желании, начальный баланс, например: /addaccount карта 15000")
+            .await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if find_account(user_entry, name).is_some() {
+        bot.send_message(msg.chat.id, "Такой счёт уже добавлен").await?;
+        return Ok(());
+    }
+
+    user_entry.accounts.push(Account { name: name.to_string(), initial_balance });
+    let is_first_account = user_entry.accounts.len() == 1;
+    if is_first_account {
+        user_entry.default_account = Some(name.to_string());
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    let mut message = format!("Счёт '{}' добавлен с балансом {:.2}. Указывайте его в трате так: обед 500 {}{}", name, initial_balance, ACCOUNT_PREFIX, name);
+    if is_first_account {
+        message.push_str("\nЭтот счёт будет использоваться по умолчанию, изменить: /defaultaccount");
+    }
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_default_account_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /defaultaccount");
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let Some(account) = find_account(user_entry, args.trim().trim_start_matches(ACCOUNT_PREFIX)) else {
+        bot.send_message(msg.chat.id, "Нет такого счёта. Список счетов: /accounts").await?;
+        return Ok(());
+    };
+
+    let name = account.name.clone();
+    user_entry.default_account = Some(name.clone());
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!("Счёт по умолчанию: '{}'", name)).await?;
+    Ok(())
+}
+
+pub async fn show_accounts(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.accounts.is_empty() {
+        bot.send_message(msg.chat.id, "У вас пока нет счетов. Добавьте счёт командой /addaccount, например: /addaccount наличные 5000").await?;
+        return Ok(());
+    }
+
+    let mut message = String::from("Ваши счета:\n\n");
+    for account in &user_entry.accounts {
+        message.push_str(&format!("{}{}: {:.2}", ACCOUNT_PREFIX, account.name, account_balance(user_entry, &account.name)));
+        if user_entry.default_account.as_ref() == Some(&account.name) {
+            message.push_str(" (по умолчанию)");
+        }
+        message.push('\n');
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_transfer_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /transfer");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let [from, to, amount] = words.as_slice() else {
+        bot.send_message(msg.chat.id, "Укажите перевод в формате: /transfer откуда куда сумма, например: /transfer карта наличные 5000").await?;
+        return Ok(());
+    };
+
+    let Some(amount) = amount.parse::<f64>().ok().filter(|amount| amount.is_finite() && *amount > 0.0) else {
+        bot.send_message(msg.chat.id, "Сумма перевода должна быть положительным числом").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let from = find_account(user_entry, from.trim_start_matches(ACCOUNT_PREFIX)).map(|a| a.name.clone());
+    let to = find_account(user_entry, to.trim_start_matches(ACCOUNT_PREFIX)).map(|a| a.name.clone());
+    let (Some(from), Some(to)) = (from, to) else {
+        bot.send_message(msg.chat.id, "Нет такого счёта. Список счетов: /accounts").await?;
+        return Ok(());
+    };
+
+    if from == to {
+        bot.send_message(msg.chat.id, "Нельзя перевести деньги на тот же счёт").await?;
+        return Ok(());
+    }
+
+    user_entry.transfers.push(Transfer { from: from.clone(), to: to.clone(), amount, date: Utc::now() });
+    let message = format!(
+        "Переведено {:.2} со счёта '{}' на счёт '{}'. Баланс: {} - {:.2}, {} - {:.2}",
+        amount,
+        from,
+        to,
+        from,
+        account_balance(user_entry, &from),
+        to,
+        account_balance(user_entry, &to)
+    );
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn send_unknown_account(bot: &Bot, chat_id: ChatId, name: &str) -> HandlerResult {
+    bot.send_message(chat_id,
+        format!("Нет счёта '{}'. Добавьте его командой /addaccount {} или посмотрите список счетов: /accounts", name, name))
+        .await?;
+    Ok(())
+}
+
+pub fn find_account<'a>(user_entry: &'a UserData, name: &str) -> Option<&'a Account> {
+    let name = name.to_lowercase();
+    user_entry.accounts.iter().find(|account| account.name.to_lowercase() == name)
+}
+
+// Находит счёт, указанный в трате, или возвращает счёт по умолчанию. Ошибка содержит название неизвестного счёта
+pub fn resolve_account(user_entry: &UserData, mentioned: Option<&str>) -> Result<Option<String>, String> {
+    match mentioned {
+        Some(name) => find_account(user_entry, name)
+            .map(|account| Some(account.name.clone()))
+            .ok_or_else(|| name.to_string()),
+        None => Ok(user_entry.default_account.clone()),
+    }
+}
+
+pub fn account_balance(user_entry: &UserData, name: &str) -> f64 {
+    let is_account = |account: &Option<String>| account.as_deref() == Some(name);
+
+    let initial = find_account(user_entry, name).map_or(0.0, |account| account.initial_balance);
+    let incomes: f64 = user_entry.incomes.iter().filter(|i| is_account(&i.account)).map(|i| i.amount).sum();
+    let expenses: f64 = user_entry.expenses.iter().filter(|e| is_account(&e.account)).map(|e| e.amount).sum();
+    let transfers_in: f64 = user_entry.transfers.iter().filter(|t| t.to == name).map(|t| t.amount).sum();
+    let transfers_out: f64 = user_entry.transfers.iter().filter(|t| t.from == name).map(|t| t.amount).sum();
+
+    initial + incomes - expenses + transfers_in - transfers_out
+}
+
+pub fn parse_account_mention(word: &str) -> Option<String> {
+    let name = word.strip_prefix(ACCOUNT_PREFIX)?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_string())
+}
@@ -10,7 +10,7 @@ pub async fn handle_message_on_add_category(
     bot: Bot,
     msg: Message,
     dialogue: MyDialogue,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     let text = if let Some(text) = msg.text() {
         text.to_owned()
@@ -24,10 +24,15 @@ pub async fn handle_message_on_add_category(
         return Ok(());
     }
 
-    let user_id = msg.from.as_ref().unwrap().id;
+    let category = normalize_category(&text);
+    if category.is_empty() {
+        send_add_category(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
-    if user_entry.categories.contains(&text) {
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    if user_entry.categories.contains(&category) {
         bot.send_message(msg.chat.id,
             "Такая категория уже добавлена")
             .await?;
@@ -35,7 +40,7 @@ pub async fn handle_message_on_add_category(
         return Ok(());
     }
 
-    send_confirm_category(bot, msg.chat.id, dialogue, text).await?;
+    send_confirm_category(bot, msg.chat.id, dialogue, category).await?;
     Ok(())
 }
 
@@ -44,7 +49,7 @@ pub async fn handle_message_on_confirm_category(
     msg: Message,
     dialogue: MyDialogue,
     category: String,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     let text = if let Some(text) = msg.text() {
         text.to_owned()
@@ -64,9 +69,8 @@ pub async fn handle_message_on_confirm_category(
     }
 
     if text == "Да" {
-        let user_id = msg.from.as_ref().unwrap().id;
         let mut data = user_data.lock().await;
-        let user_entry = get_user_entry(&mut data, user_id);
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
         if user_entry.categories.contains(&category) {
             bot.send_message(msg.chat.id,
                 "Такая категория уже добавлена")
@@ -75,7 +79,7 @@ pub async fn handle_message_on_confirm_category(
             return Ok(());
         }
 
-        user_entry.categories.push(category);
+        add_category_with_parents(user_entry, &category);
         if let Err(e) = save_user_data(&data).await {
             warn!("Save data error: {}", e);
         }
@@ -103,7 +107,7 @@ async fn send_add_category(bot: Bot, chat_id: ChatId, dialogue: MyDialogue) -> H
         .one_time_keyboard();
 
     bot.send_message(chat_id,
-        "Введите название для новой категории трат:")
+        "Введите название для новой категории трат. Чтобы создать подкатегорию, укажите родителя через '/', например: Еда/Продукты")
         .reply_markup(keyboard)
         .await?;
 
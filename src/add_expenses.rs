@@ -3,7 +3,7 @@ use crate::*;
 pub async fn start_add_expense(bot: Bot, msg: Message, dialogue: MyDialogue) -> HandlerResult {
     info!("Got command /addexpense");
     bot.send_message(msg.chat.id,
-        "Введите трату в формате: описание цена, например: продукты 15.5")
+        "Введите трату в формате: описание цена, например: продукты 15.5. Можно добавить теги: ужин 2500 #отпуск #сочи, или вставить строку из QR-кода чека")
         .await?;
 
     info!("Changing state to AddExpense");
@@ -15,25 +15,91 @@ pub async fn handle_message_expense(
     bot: Bot,
     msg: Message,
     dialogue: MyDialogue,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    me: Me,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
-    if let Some(text) = msg.text() {
+    // Подпись к фото или документу чека обрабатываем так же, как текст
+    if let Some(text) = msg.text().or(msg.caption()).map(|text| strip_bot_mention(text, &me)) {
+        let text = text.as_str();
         info!("Received message: {}", text);
 
-        if let Some((description, amount)) = parse_expense(text) {
-            info!("Parsed expense: {}, {}", description, amount);
+        if let Some(mut pending_income) = parse_income(text) {
+            info!("Parsed income: {}, {}", pending_income.description, pending_income.amount);
 
-            let user_id = msg.from.as_ref().unwrap().id;
             let mut data = user_data.lock().await;
-            let user_entry = get_user_entry(&mut data, user_id);
+            let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+            match resolve_account(user_entry, pending_income.account.as_deref()) {
+                Ok(account) => pending_income.account = account,
+                Err(name) => {
+                    send_unknown_account(&bot, msg.chat.id, &name).await?;
+                    return Ok(());
+                }
+            }
+
+            send_select_income_category(bot, msg.chat.id, user_entry, dialogue, pending_income).await?;
+            return Ok(());
+        }
+
+        if let Some(receipt) = parse_receipt_qr(text) {
+            info!("Parsed receipt QR: {}, {}", receipt.date, receipt.total);
+
+            let mut data = user_data.lock().await;
+            let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+            if let Some(mut pending_expense) = receipt.to_pending_expense(user_entry.timezone()) {
+                pending_expense.message_id = Some(msg.id);
+                pending_expense.account = user_entry.default_account.clone();
+                send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
+                return Ok(());
+            }
+        }
+
+        if let Some(notification) = parse_bank_notification(text) {
+            info!("Parsed bank notification: {}, {}", notification.merchant, notification.amount);
+
+            let mut data = user_data.lock().await;
+            let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+            let mut pending_expense = notification.to_pending_expense(user_entry);
+            pending_expense.message_id = Some(msg.id);
+            bot.send_message(msg.chat.id, notification.describe()).await?;
+
+            match categorize_by_rules(user_entry, &notification.merchant, None) {
+                Some(category) => send_confirm_expense(bot, msg.chat.id, pending_expense, category, user_entry.timezone(), dialogue).await?,
+                None => send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?,
+            }
+            return Ok(());
+        }
+
+        if let Some(mut pending_expense) = parse_expense(text) {
+            info!("Parsed expense: {}, {}", pending_expense.description, pending_expense.amount);
+            pending_expense.message_id = Some(msg.id);
+            pending_expense.attachment = attachment_from_message(&msg);
+
+            let mut data = user_data.lock().await;
+            let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+            match resolve_account(user_entry, pending_expense.account.as_deref()) {
+                Ok(account) => pending_expense.account = account,
+                Err(name) => {
+                    send_unknown_account(&bot, msg.chat.id, &name).await?;
+                    return Ok(());
+                }
+            }
             
-            send_select_category(bot, msg.chat.id, user_entry, dialogue, description, amount).await?;
+            send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
             return Ok(());
         }
     }
 
     info!("Expense didn't parsed");
-    bot.send_message(msg.chat.id, "Пожалуйста, укажите трату в формате 'описание сумма', например: 'продукты 15.5'").await?;
+    if attachment_from_message(&msg).is_some() {
+        bot.send_message(msg.chat.id, "Чтобы добавить трату по фото чека, подпишите его в формате 'описание сумма', например: 'продукты 1530'").await?;
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, "Пожалуйста, укажите трату в формате 'описание сумма #теги', например: 'продукты 15.5' или 'ужин 2500 #отпуск'").await?;
     Ok(())
 }
 
@@ -41,8 +107,8 @@ pub async fn handle_message_on_select_category(
     bot: Bot,
     msg: Message,
     dialogue: MyDialogue,
-    pending_expense: (String, f64),
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    pending_expense: PendingExpense,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     info!("Got message with category");
     let text = if let Some(text) = msg.text() {
@@ -59,32 +125,37 @@ pub async fn handle_message_on_select_category(
         return Ok(());
     }
 
-    let user_id = msg.from.as_ref().unwrap().id;
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
-    let (description, amount) = pending_expense;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if let Ok(id) = text.parse::<usize>() {
         info!("Parsed id: {}", id);
         if id >= user_entry.categories.len() {
             info!("No such id");
             bot.send_message(msg.chat.id, "Нет категории с таким id").await?;
-            send_select_category(bot, msg.chat.id, user_entry, dialogue, description, amount).await?;
+            send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
             return Ok(());
         }
 
         let category = &user_entry.categories[id];
         info!("Got category by id: {}", category);
-        send_confirm_expense(bot, msg.chat.id, description, amount, category.to_string(), dialogue).await?;
+        send_confirm_expense(bot, msg.chat.id, pending_expense, category.to_string(), user_entry.timezone(), dialogue).await?;
         return Ok(());
     }
     
-    info!("Got category: {}", text);
-    send_confirm_expense(bot, msg.chat.id, description, amount, text, dialogue).await?;
-    return Ok(());
+    let category = normalize_category(&text);
+    if category.is_empty() {
+        info!("Empty category name");
+        send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
+        return Ok(());
+    }
+
+    info!("Got category: {}", category);
+    send_confirm_expense(bot, msg.chat.id, pending_expense, category, user_entry.timezone(), dialogue).await?;
+    Ok(())
 
     // info!("Go back to select category");
-    // send_select_category(bot, msg.chat.id, user_entry, dialogue, description, amount).await?;
+    // send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
     // Ok(())
 }
 
@@ -92,8 +163,9 @@ pub async fn handle_message_on_confirm_expense(
     bot: Bot,
     msg: Message,
     dialogue: MyDialogue,
-    (pending_expense, category): ((String, f64), String),
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    (mut pending_expense, category): (PendingExpense, String),
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>,
+    config: Arc<Config>
 ) -> HandlerResult {
     info!("Got message on confirm expense");
     let text = if let Some(text) = msg.text() {
@@ -111,46 +183,73 @@ pub async fn handle_message_on_confirm_expense(
     }
 
     let user_id = msg.from.as_ref().unwrap().id;
+
+    // Чек скачиваем до блокировки данных, чтобы не держать их на время загрузки
+    let attachment = match pending_expense.attachment.take() {
+        Some(attachment) if text == "Да" => Some(store_attachment(&bot, &config, user_id, attachment).await),
+        attachment => {
+            pending_expense.attachment = attachment;
+            None
+        }
+    };
+
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
-    let (description, amount) = pending_expense;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if text == "Назад" {
         info!("Go back to select category");
-        send_select_category(bot, msg.chat.id, user_entry, dialogue, description, amount).await?;
+        send_select_category(bot, msg.chat.id, user_entry, dialogue, pending_expense).await?;
         return Ok(());
     }
 
     if text == "Да" {
         info!("Adding expense");
         let expense = Expense {
-            description,
-            amount,
+            description: pending_expense.description,
+            amount: pending_expense.amount,
             category: category.clone(),
-            date: Utc::now()
+            date: pending_expense.date.unwrap_or_else(Utc::now),
+            tags: pending_expense.tags,
+            note: None,
+            attachment,
+            message_ids: pending_expense.message_id.map(|message_id| (msg.chat.id, message_id)).into_iter().collect(),
+            account: pending_expense.account,
+            author: msg.from.as_ref().map(|user| user.id),
+            split: Vec::new(),
         };
 
+        let budget_report = budget_report_after_expense(user_entry, &expense);
         user_entry.expenses.push(expense);
+        let expense_index = user_entry.expenses.len() - 1;
+        record_expenses_added(ExpenseSource::Manual, 1);
+        add_category_with_parents(user_entry, &category);
         
-        if !user_entry.categories.contains(&category) {
-            user_entry.categories.push(category.clone());
-        }
-        
+        // Сначала сохраняем трату, чтобы она не потерялась, если подтверждение не отправится
         if let Err(e) = save_user_data(&data).await {
             warn!("Save data error: {}", e);
         }
 
-        bot.send_message(
+        let confirmation = bot.send_message(
             msg.chat.id,
-            format!("Трата добавлена в категорию '{}'", category)
+            format!("Трата добавлена в категорию '{}'. Ответьте на это сообщение текстом или фото чека, чтобы прикрепить их к трате", category)
         ).await?;
+
+        get_user_entry(&mut data, msg.chat.id).expenses[expense_index].message_ids.push((msg.chat.id, confirmation.id));
+        if let Err(e) = save_user_data(&data).await {
+            warn!("Save data error: {}", e);
+        }
+
+        if let Some(budget_report) = budget_report {
+            bot.send_message(msg.chat.id, budget_report).await?;
+        }
+
         enter_default_state(bot, msg.chat.id, dialogue).await?;
         return Ok(());
     }
     
     info!("Not parsed text");
     bot.send_message(msg.chat.id, "Пожалуйста, подтвердите или отмените добавление траты, используя предложенные варианты").await?;
-    send_confirm_expense(bot, msg.chat.id, description, amount, category, dialogue).await?;
+    send_confirm_expense(bot, msg.chat.id, pending_expense, category, user_entry.timezone(), dialogue).await?;
     Ok(())
 }
 
@@ -159,8 +258,7 @@ async fn send_select_category(
     chat_id: ChatId,
     user_entry: &mut UserData,
     dialogue: MyDialogue,
-    description: String,
-    amount: f64
+    pending_expense: PendingExpense
 ) -> HandlerResult {
     info!("Sending select category");
     let keyboard = KeyboardMarkup::new(
@@ -185,22 +283,22 @@ async fn send_select_category(
     bot.send_message(chat_id, message).await?;
     bot.send_message(
         chat_id,
-        format!("Вы ввели трату '{}' на сумму {:.2}. Введите Id или название категории из списка, или введите название новой категории", description, amount)
+        format!("Вы ввели трату {}. Введите Id или название категории из списка, или введите название новой категории. Подкатегории указываются через '/', например: Еда/Рестораны", describe_pending_expense(&pending_expense, user_entry.timezone()))
     )
     .reply_markup(keyboard)
     .await?;
 
     info!("Changing state to SelectCategory");
-    dialogue.update(State::SelectCategory { pending_expense: (description, amount) }).await?;
+    dialogue.update(State::SelectCategory { pending_expense }).await?;
     Ok(())
 }
 
 async fn send_confirm_expense(
     bot: Bot,
     chat_id: ChatId,
-    description: String,
-    amount: f64,
+    pending_expense: PendingExpense,
     category: String,
+    timezone: FixedOffset,
     dialogue: MyDialogue
 ) -> HandlerResult {
     info!("Sending confirm expense");
@@ -211,27 +309,59 @@ async fn send_confirm_expense(
 
     bot.send_message(
         chat_id,
-        format!("Подтвердите добавление траты '{}' на сумму {:.2} в категорию {}", description, amount, category)
+        format!("Подтвердите добавление траты {} в категорию {}", describe_pending_expense(&pending_expense, timezone), category)
     )
     .reply_markup(keyboard)
     .await?;
 
     info!("Changing state to ConfirmAddExpense");
-    dialogue.update(State::ConfirmAddExpense{ pending_expense: (description, amount), category: category.to_owned() }).await?;
+    dialogue.update(State::ConfirmAddExpense{ pending_expense, category: category.to_owned() }).await?;
     Ok(())
 }
 
-fn parse_expense(text: &str) -> Option<(String, f64)> {
-    let words: Vec<&str> = text.split_whitespace().collect();
+pub fn describe_pending_expense(pending_expense: &PendingExpense, timezone: FixedOffset) -> String {
+    let mut description = format!("'{}' на сумму {:.2}", pending_expense.description, pending_expense.amount);
+    if let Some(date) = pending_expense.date {
+        description.push_str(&format!(" от {}", date.with_timezone(&timezone).format("%d.%m.%y %H:%M")));
+    }
+    if !pending_expense.tags.is_empty() {
+        description.push_str(&format!(" с тегами {}", format_tags(&pending_expense.tags)));
+    }
+    if let Some(account) = &pending_expense.account {
+        description.push_str(&format!(" со счёта '{}'", account));
+    }
+    if pending_expense.attachment.is_some() {
+        description.push_str(" 📎");
+    }
+    description
+}
+
+// Формат: "описание сумма #тег1 #тег2 @счёт", теги и счёт могут стоять в любом месте
+pub fn parse_expense(text: &str) -> Option<PendingExpense> {
+    let mut tags = Vec::new();
+    let mut account = None;
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some(tag) = parse_tag(word) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        } else if let Some(name) = parse_account_mention(word) {
+            account = Some(name);
+        } else {
+            words.push(word);
+        }
+    }
 
     if words.len() < 2 {
         return None;
     }
 
-    if let Ok(amount) = words.last().unwrap().parse::<f64>() {
+    // "inf" и "nan" тоже разбираются как числа, но в JSON их не сохранить
+    if let Some(amount) = words.last().unwrap().parse::<f64>().ok().filter(|amount| amount.is_finite()) {
         let description = words[..words.len() - 1].join(" ");
-        return Some((description, amount));
+        return Some(PendingExpense { description, amount, tags, message_id: None, attachment: None, date: None, account });
     }
 
     None
-}
\ No newline at end of file
+}
@@ -0,0 +1,330 @@
+use crate::*;
+
+const INCOME_PREFIX: char = '+';
+
+pub async fn start_add_income(bot: Bot, msg: Message, dialogue: MyDialogue) -> HandlerResult {
+    info!("Got command /addincome");
+    bot.send_message(msg.chat.id,
+        "Введите доход в формате: описание сумма, например: зарплата 150000")
+        .await?;
+
+    info!("Changing state to AddIncome");
+    dialogue.update(State::AddIncome).await?;
+    Ok(())
+}
+
+pub async fn handle_message_income(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    if let Some(text) = msg.text() {
+        info!("Received income message: {}", text);
+
+        let text = text.trim();
+        let text = text.strip_prefix(INCOME_PREFIX).unwrap_or(text);
+        if let Some(mut pending_income) = parse_income_text(text) {
+            let mut data = user_data.lock().await;
+            let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+            match resolve_account(user_entry, pending_income.account.as_deref()) {
+                Ok(account) => pending_income.account = account,
+                Err(name) => {
+                    send_unknown_account(&bot, msg.chat.id, &name).await?;
+                    return Ok(());
+                }
+            }
+
+            send_select_income_category(bot, msg.chat.id, user_entry, dialogue, pending_income).await?;
+            return Ok(());
+        }
+    }
+
+    info!("Income didn't parsed");
+    bot.send_message(msg.chat.id, "Пожалуйста, укажите доход в формате 'описание сумма', например: 'зарплата 150000'").await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_select_income_category(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    pending_income: PendingIncome,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got message with income category");
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    if text == "Назад" {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if let Ok(id) = text.parse::<usize>() {
+        if id >= user_entry.income_categories.len() {
+            bot.send_message(msg.chat.id, "Нет категории с таким id").await?;
+            send_select_income_category(bot, msg.chat.id, user_entry, dialogue, pending_income).await?;
+            return Ok(());
+        }
+
+        let category = user_entry.income_categories[id].clone();
+        send_confirm_income(bot, msg.chat.id, pending_income, category, dialogue).await?;
+        return Ok(());
+    }
+
+    let category = text.trim().to_string();
+    if category.is_empty() {
+        send_select_income_category(bot, msg.chat.id, user_entry, dialogue, pending_income).await?;
+        return Ok(());
+    }
+
+    send_confirm_income(bot, msg.chat.id, pending_income, category, dialogue).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_confirm_income(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    (pending_income, category): (PendingIncome, String),
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got message on confirm income");
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    if text == "Отменить" {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if text == "Назад" {
+        send_select_income_category(bot, msg.chat.id, user_entry, dialogue, pending_income).await?;
+        return Ok(());
+    }
+
+    if text == "Да" {
+        info!("Adding income");
+        user_entry.incomes.push(Income {
+            description: pending_income.description,
+            amount: pending_income.amount,
+            category: category.clone(),
+            date: Utc::now(),
+            account: pending_income.account,
+        });
+
+        if !user_entry.income_categories.contains(&category) {
+            user_entry.income_categories.push(category.clone());
+        }
+
+        let goal_keyboard = goal_contribution_keyboard(user_entry, user_entry.incomes.len() - 1);
+        if let Err(e) = save_user_data(&data).await {
+            warn!("Save data error: {}", e);
+        }
+
+        bot.send_message(msg.chat.id, format!("Доход добавлен в категорию '{}'", category)).await?;
+        if let Some(keyboard) = goal_keyboard {
+            bot.send_message(msg.chat.id, "Отложить часть дохода на цель?")
+                .reply_markup(keyboard)
+                .await?;
+        }
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, "Пожалуйста, подтвердите или отмените добавление дохода, используя предложенные варианты").await?;
+    send_confirm_income(bot, msg.chat.id, pending_income, category, dialogue).await?;
+    Ok(())
+}
+
+pub async fn show_incomes(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.incomes.is_empty() {
+        bot.send_message(msg.chat.id, "Вы пока не записали ни одного дохода").await?;
+        return Ok(());
+    }
+
+    let timezone = user_entry.timezone();
+    let filter = ExpenseFilter::parse(&args, timezone);
+    let incomes: Vec<&Income> = user_entry.incomes.iter()
+        .filter(|income| filter.matches_income(income))
+        .collect();
+
+    if incomes.is_empty() {
+        bot.send_message(msg.chat.id, format!("Нет доходов{}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    if incomes.len() > MAX_ITEMS_IN_MESSAGE {
+        bot.send_message(msg.chat.id,
+            format!("Показываем {} из {} ваших доходов", MAX_ITEMS_IN_MESSAGE, incomes.len()))
+            .await?;
+    }
+
+    let mut message = format!("Ваши доходы{}:\n\n", filter.describe());
+    for income in incomes.into_iter().take(MAX_ITEMS_IN_MESSAGE) {
+        message.push_str(&format!(
+            "[{}] - **{}**: {}, на сумму: {:.2}\n",
+            income.date.with_timezone(&timezone).format("%d.%m.%y %H:%M"),
+            income.category,
+            income.description,
+            income.amount
+        ));
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_balance(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let total_income: f64 = user_entry.incomes.iter()
+        .filter(|income| filter.matches_income(income))
+        .map(|income| income.amount)
+        .sum();
+    let total_expenses: f64 = user_entry.expenses.iter()
+        .filter(|expense| filter.matches(expense))
+        .map(|expense| expense.amount)
+        .sum();
+    let balance = total_income - total_expenses;
+
+    let mut message = format!(
+        "Баланс{}:\n\nДоходы: {:.2}\nРасходы: {:.2}\nИтого: {:+.2}\n",
+        filter.describe(),
+        total_income,
+        total_expenses,
+        balance
+    );
+    if total_income > 0.0 && balance > 0.0 {
+        message.push_str(&format!("\nУдалось сохранить {:.0}% дохода\n", balance / total_income * 100.0));
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn send_select_income_category(
+    bot: Bot,
+    chat_id: ChatId,
+    user_entry: &mut UserData,
+    dialogue: MyDialogue,
+    pending_income: PendingIncome
+) -> HandlerResult {
+    let keyboard = KeyboardMarkup::new(
+        vec![vec![KeyboardButton::new("Назад")]])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    let mut message = String::from("Ваши категории доходов:\n\n");
+    for (i, category) in user_entry.income_categories.iter().take(MAX_ITEMS_IN_MESSAGE).enumerate() {
+        message.push_str(&format!(
+            "Id: {}, название: {}\n",
+            i,
+            category));
+    }
+
+    bot.send_message(chat_id, message).await?;
+    bot.send_message(
+        chat_id,
+        format!("Вы ввели доход {}. Введите Id или название категории из списка, или введите название новой категории", describe_pending_income(&pending_income))
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    info!("Changing state to SelectIncomeCategory");
+    dialogue.update(State::SelectIncomeCategory { pending_income }).await?;
+    Ok(())
+}
+
+async fn send_confirm_income(
+    bot: Bot,
+    chat_id: ChatId,
+    pending_income: PendingIncome,
+    category: String,
+    dialogue: MyDialogue
+) -> HandlerResult {
+    let keyboard = KeyboardMarkup::new(
+        vec![vec![KeyboardButton::new("Отменить"), KeyboardButton::new("Назад"), KeyboardButton::new("Да")]])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    bot.send_message(
+        chat_id,
+        format!("Подтвердите добавление дохода {} в категорию {}", describe_pending_income(&pending_income), category)
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    info!("Changing state to ConfirmAddIncome");
+    dialogue.update(State::ConfirmAddIncome { pending_income, category }).await?;
+    Ok(())
+}
+
+fn describe_pending_income(pending_income: &PendingIncome) -> String {
+    let mut description = format!("'{}' на сумму {:.2}", pending_income.description, pending_income.amount);
+    if let Some(account) = &pending_income.account {
+        description.push_str(&format!(" на счёт '{}'", account));
+    }
+    description
+}
+
+// Формат: "+описание сумма @счёт"
+pub fn parse_income(text: &str) -> Option<PendingIncome> {
+    let text = text.trim().strip_prefix(INCOME_PREFIX)?;
+    parse_income_text(text)
+}
+
+fn parse_income_text(text: &str) -> Option<PendingIncome> {
+    let mut account = None;
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        match parse_account_mention(word) {
+            Some(name) => account = Some(name),
+            None => words.push(word),
+        }
+    }
+
+    if words.len() < 2 {
+        return None;
+    }
+
+    // "inf" и "nan" тоже разбираются как числа, но в JSON их не сохранить
+    let amount = words.last().unwrap().parse::<f64>().ok()?;
+    if !amount.is_finite() || amount <= 0.0 {
+        return None;
+    }
+
+    let description = words[..words.len() - 1].join(" ");
+    Some(PendingIncome { description, amount, account })
+}
@@ -0,0 +1,195 @@
+use crate::*;
+
+use std::time::Duration;
+
+// Пауза между сообщениями рассылки, чтобы не упереться в лимиты Telegram
+const BROADCAST_DELAY: Duration = Duration::from_millis(50);
+
+pub fn load_access_data() -> Result<AccessData, Box<dyn Error>> {
+    if !Path::new(ACCESS_FILE_PATH).exists() {
+        return Ok(AccessData::default());
+    }
+
+    let file_content = fs::read_to_string(ACCESS_FILE_PATH)?;
+    Ok(serde_json::from_str(&file_content)?)
+}
+
+pub async fn save_access_data(access_data: &AccessData) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(access_data)?;
+    fs::write(ACCESS_FILE_PATH, json)?;
+    Ok(())
+}
+
+pub fn is_admin_message(msg: Message, config: Arc<Config>) -> bool {
+    msg.from.as_ref().is_some_and(|user| config.is_admin(user.id))
+}
+
+// Проверяется до всего остального, чтобы для посторонних не создавались записи в users_data.json
+pub async fn is_access_denied(update: Update, config: Arc<Config>, access_data: Arc<Mutex<AccessData>>) -> bool {
+    let Some(user) = update.from() else {
+        return false;
+    };
+    if config.is_admin(user.id) {
+        return false;
+    }
+
+    let access_data = access_data.lock().await;
+    access_data.blocked.contains(&user.id) || (config.allowlist && !access_data.allowed.contains(&user.id))
+}
+
+pub async fn handle_access_denied(bot: Bot, update: Update, access_data: Arc<Mutex<AccessData>>) -> HandlerResult {
+    let Some(user) = update.from() else {
+        return Ok(());
+    };
+    info!("Access denied for user {}", user.id);
+
+    // Заблокированным не отвечаем, остальным подсказываем, что передать администратору
+    if access_data.lock().await.blocked.contains(&user.id) {
+        return Ok(());
+    }
+    if let Some(chat) = update.chat().filter(|chat| chat.is_private()) {
+        bot.send_message(chat.id, format!("Бот работает в закрытом режиме. Чтобы получить доступ, передайте администратору ваш id: {}", user.id)).await?;
+    }
+    Ok(())
+}
+
+pub async fn show_admin_stats(
+    bot: Bot,
+    msg: Message,
+    config: Arc<Config>,
+    access_data: Arc<Mutex<AccessData>>,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /stats");
+    let data = user_data.lock().await;
+    let users = data.keys().filter(|chat_id| chat_id.is_user()).count();
+    let groups = data.len() - users;
+    let expenses: usize = data.values().map(|user_entry| user_entry.expenses.len()).sum();
+    drop(data);
+
+    let data_size = fs::metadata(DATA_FILE_PATH).map(|metadata| metadata.len()).unwrap_or_default();
+    let attachments_size = config.attachments_dir.as_deref().map(directory_size).unwrap_or_default();
+    let access_data = access_data.lock().await;
+
+    let mut message = format!(
+        "Пользователей: {}\nГрупповых чатов: {}\nТрат: {}\nФайл данных: {}\n",
+        users,
+        groups,
+        expenses,
+        format_size(data_size)
+    );
+    if config.attachments_dir.is_some() {
+        message.push_str(&format!("Вложения: {}\n", format_size(attachments_size)));
+    }
+    message.push_str(&format!("Заблокировано: {}\n", access_data.blocked.len()));
+    if config.allowlist {
+        message.push_str(&format!("Закрытый режим, разрешено: {}\n", access_data.allowed.len()));
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_broadcast_command(
+    bot: Bot,
+    msg: Message,
+    text: String,
+    access_data: Arc<Mutex<AccessData>>,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /broadcast");
+    if text.trim().is_empty() {
+        bot.send_message(msg.chat.id, "Укажите текст рассылки: /broadcast текст").await?;
+        return Ok(());
+    }
+
+    let blocked = access_data.lock().await.blocked.clone();
+    let chat_ids: Vec<ChatId> = user_data.lock().await.keys()
+        .filter(|chat_id| chat_id.as_user().is_none_or(|user_id| !blocked.contains(&user_id)))
+        .copied()
+        .collect();
+
+    let mut failed = 0;
+    for chat_id in &chat_ids {
+        if let Err(e) = bot.send_message(*chat_id, text.trim()).await {
+            warn!("Broadcast to {} failed: {}", chat_id, e);
+            record_telegram_error();
+            failed += 1;
+        }
+        tokio::time::sleep(BROADCAST_DELAY).await;
+    }
+
+    bot.send_message(msg.chat.id, format!("Рассылка отправлена: {} из {}", chat_ids.len() - failed, chat_ids.len())).await?;
+    Ok(())
+}
+
+pub async fn handle_block_command(bot: Bot, msg: Message, args: String, access_data: Arc<Mutex<AccessData>>) -> HandlerResult {
+    update_access_list(bot, msg, &args, access_data, |access_data| &mut access_data.blocked, true, "заблокирован").await
+}
+
+pub async fn handle_unblock_command(bot: Bot, msg: Message, args: String, access_data: Arc<Mutex<AccessData>>) -> HandlerResult {
+    update_access_list(bot, msg, &args, access_data, |access_data| &mut access_data.blocked, false, "разблокирован").await
+}
+
+pub async fn handle_allow_command(bot: Bot, msg: Message, args: String, access_data: Arc<Mutex<AccessData>>) -> HandlerResult {
+    update_access_list(bot, msg, &args, access_data, |access_data| &mut access_data.allowed, true, "добавлен в список разрешённых").await
+}
+
+pub async fn handle_disallow_command(bot: Bot, msg: Message, args: String, access_data: Arc<Mutex<AccessData>>) -> HandlerResult {
+    update_access_list(bot, msg, &args, access_data, |access_data| &mut access_data.allowed, false, "убран из списка разрешённых").await
+}
+
+pub async fn handle_admin_help_command(bot: Bot, msg: Message) -> HandlerResult {
+    bot.send_message(msg.chat.id, AdminCommand::descriptions().to_string()).await?;
+    Ok(())
+}
+
+async fn update_access_list(
+    bot: Bot,
+    msg: Message,
+    args: &str,
+    access_data: Arc<Mutex<AccessData>>,
+    list: fn(&mut AccessData) -> &mut Vec<UserId>,
+    add: bool,
+    action: &str
+) -> HandlerResult {
+    let Ok(user_id) = args.trim().parse::<u64>().map(UserId) else {
+        bot.send_message(msg.chat.id, "Укажите id пользователя, например: /block 123456789").await?;
+        return Ok(());
+    };
+    info!("Access list update: user {} {}", user_id, action);
+
+    let mut access_data = access_data.lock().await;
+    let users = list(&mut access_data);
+    users.retain(|id| *id != user_id);
+    if add {
+        users.push(user_id);
+    }
+
+    if let Err(e) = save_access_data(&access_data).await {
+        warn!("Save access data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!("Пользователь {} {}", user_id, action)).await?;
+    Ok(())
+}
+
+fn directory_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries.filter_map(Result::ok)
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            _ => entry.metadata().map(|metadata| metadata.len()).unwrap_or_default(),
+        })
+        .sum()
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} Б", bytes),
+        1024..1_048_576 => format!("{:.1} КБ", bytes as f64 / 1024.0),
+        _ => format!("{:.1} МБ", bytes as f64 / 1_048_576.0),
+    }
+}
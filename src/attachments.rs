@@ -0,0 +1,163 @@
+use crate::*;
+
+pub async fn is_reply_to_expense(msg: Message, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) -> bool {
+    let Some(reply_to) = msg.reply_to_message() else {
+        return false;
+    };
+
+    let data = user_data.lock().await;
+    has_expense_for_message(&data, msg.chat.id, reply_to.id)
+}
+
+pub async fn handle_expense_reply(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>,
+    config: Arc<Config>,
+    me: Me
+) -> HandlerResult {
+    info!("Got reply to expense message");
+    let user_id = msg.from.as_ref().unwrap().id;
+    let reply_to_id = msg.reply_to_message().unwrap().id;
+
+    // Трата могла быть удалена, пока пользователь писал ответ: проверяем до загрузки файла, чтобы не хранить лишнего
+    let data = user_data.lock().await;
+    let has_expense = has_expense_for_message(&data, msg.chat.id, reply_to_id);
+    drop(data);
+    if !has_expense {
+        bot.send_message(msg.chat.id, "Не удалось найти трату для этого сообщения").await?;
+        return Ok(());
+    }
+
+    let note = msg.text().or(msg.caption())
+        .map(|text| strip_bot_mention(text, &me))
+        .filter(|note| !note.is_empty());
+    let attachment = match attachment_from_message(&msg) {
+        Some(attachment) => Some(store_attachment(&bot, &config, user_id, attachment).await),
+        None => None,
+    };
+
+    if note.is_none() && attachment.is_none() {
+        bot.send_message(msg.chat.id, "К трате можно прикрепить только текст, фото или документ").await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let Some(index) = find_expense_by_message(user_entry, msg.chat.id, reply_to_id) else {
+        bot.send_message(msg.chat.id, "Не удалось найти трату для этого сообщения").await?;
+        return Ok(());
+    };
+
+    let expense = &mut user_entry.expenses[index];
+    let mut message = String::new();
+    if let Some(note) = note {
+        expense.note = Some(note);
+        message.push_str(&format!("Заметка сохранена для траты '{}'\n", expense.description));
+    }
+    if let Some(attachment) = attachment {
+        expense.attachment = Some(attachment);
+        message.push_str(&format!("Вложение прикреплено к трате '{}'. Посмотреть: /attachment {}\n", expense.description, index));
+    }
+    expense.message_ids.push((msg.chat.id, msg.id));
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_attachment(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let Ok(id) = args.trim().parse::<usize>() else {
+        bot.send_message(msg.chat.id, "Укажите номер траты из /allexpenses, например: /attachment 3").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let Some(expense) = user_entry.expenses.get(id) else {
+        bot.send_message(msg.chat.id, "Нет траты с таким номером").await?;
+        return Ok(());
+    };
+
+    if expense.note.is_none() && expense.attachment.is_none() {
+        bot.send_message(msg.chat.id, "У этой траты нет заметки или вложения").await?;
+        return Ok(());
+    }
+
+    if let Some(note) = &expense.note {
+        bot.send_message(msg.chat.id, format!("Заметка к трате '{}': {}", expense.description, note)).await?;
+    }
+
+    if let Some(attachment) = &expense.attachment {
+        let file = InputFile::file_id(attachment.file_id.clone());
+        match attachment.kind {
+            AttachmentKind::Photo => bot.send_photo(msg.chat.id, file).await?,
+            AttachmentKind::Document => bot.send_document(msg.chat.id, file).await?,
+        };
+    }
+    Ok(())
+}
+
+pub fn attachment_from_message(msg: &Message) -> Option<Attachment> {
+    if let Some(photo) = msg.photo().and_then(|sizes| sizes.iter().max_by_key(|size| size.width * size.height)) {
+        return Some(Attachment { file_id: photo.file.id.clone(), kind: AttachmentKind::Photo, local_path: None });
+    }
+
+    if let Some(document) = msg.document() {
+        return Some(Attachment { file_id: document.file.id.clone(), kind: AttachmentKind::Document, local_path: None });
+    }
+
+    None
+}
+
+// Скачивает вложение в папку из настроек, если она задана. Ошибка скачивания не мешает сохранить file id
+pub async fn store_attachment(bot: &Bot, config: &Config, user_id: UserId, mut attachment: Attachment) -> Attachment {
+    let Some(attachments_dir) = &config.attachments_dir else {
+        return attachment;
+    };
+
+    match download_attachment(bot, attachments_dir, user_id, &attachment).await {
+        Ok(path) => attachment.local_path = Some(path),
+        Err(e) => warn!("Attachment download error: {}", e),
+    }
+    attachment
+}
+
+async fn download_attachment(
+    bot: &Bot,
+    attachments_dir: &Path,
+    user_id: UserId,
+    attachment: &Attachment
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let file = bot.get_file(attachment.file_id.clone()).await?;
+    let extension = Path::new(&file.path).extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("bin");
+
+    let user_dir = attachments_dir.join(user_id.to_string());
+    tokio::fs::create_dir_all(&user_dir).await?;
+
+    let path = user_dir.join(format!("{}.{}", file.meta.unique_id, extension));
+    let mut destination = tokio::fs::File::create(&path).await?;
+    bot.download_file(&file.path, &mut destination).await?;
+
+    info!("Attachment saved to {}", path.display());
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn has_expense_for_message(user_data: &HashMap<ChatId, UserData>, chat_id: ChatId, message_id: MessageId) -> bool {
+    user_data.get(&ledger_id(user_data, chat_id))
+        .is_some_and(|user_entry| find_expense_by_message(user_entry, chat_id, message_id).is_some())
+}
+
+fn find_expense_by_message(user_entry: &UserData, chat_id: ChatId, message_id: MessageId) -> Option<usize> {
+    user_entry.expenses.iter().position(|expense| expense.message_ids.contains(&(chat_id, message_id)))
+}
@@ -0,0 +1,237 @@
+use crate::*;
+
+use chrono::Duration;
+
+const BANK_DUPLICATE_WINDOW_DAYS: i64 = 2;
+const MCC_MARKER: &str = "MCC";
+// Переводы между своими счетами - не траты
+const INTERNAL_TRANSFER_MARKERS: [&str; 4] = ["между своими", "между счетами", "перевод себе", "собственных счетов"];
+
+#[derive(Debug, Clone, Copy)]
+enum AmountColumn {
+    // Расходы со знаком минус, поступления с плюсом
+    Signed(&'static str),
+    // Отдельный столбец только с расходами
+    Debit(&'static str),
+}
+
+// Столбцы выписки конкретного банка. Банк определяем по тому, что в заголовках есть все нужные столбцы
+#[derive(Debug)]
+pub struct BankLayout {
+    name: &'static str,
+    date: &'static str,
+    amount: AmountColumn,
+    description: &'static str,
+    category: Option<&'static str>,
+    mcc: Option<&'static str>,
+    // Столбец статуса и значение для проведённых операций
+    status: Option<(&'static str, &'static str)>,
+}
+
+const BANK_LAYOUTS: [BankLayout; 3] = [
+    BankLayout {
+        name: "Т-Банк",
+        date: "Дата операции",
+        amount: AmountColumn::Signed("Сумма операции"),
+        description: "Описание",
+        category: Some("Категория"),
+        mcc: Some("MCC"),
+        status: Some(("Статус", "OK")),
+    },
+    BankLayout {
+        name: "Сбербанк",
+        date: "Дата операции",
+        amount: AmountColumn::Signed("Сумма в валюте счёта"),
+        description: "Описание",
+        category: Some("Категория"),
+        mcc: None,
+        status: None,
+    },
+    BankLayout {
+        name: "Альфа-Банк",
+        date: "Дата операции",
+        amount: AmountColumn::Debit("Расход"),
+        description: "Описание операции",
+        category: None,
+        mcc: None,
+        status: None,
+    },
+];
+
+impl BankLayout {
+    fn columns(&self) -> impl Iterator<Item = &'static str> {
+        let amount = match self.amount {
+            AmountColumn::Signed(column) | AmountColumn::Debit(column) => column,
+        };
+        [Some(self.date), Some(amount), Some(self.description), self.category, self.mcc, self.status.map(|(column, _)| column)]
+            .into_iter()
+            .flatten()
+    }
+}
+
+pub fn detect_bank_layout(headers: &[String]) -> Option<&'static BankLayout> {
+    BANK_LAYOUTS.iter()
+        .find(|layout| layout.columns().all(|column| headers.iter().any(|header| header.eq_ignore_ascii_case(column))))
+}
+
+pub fn convert_bank_statement(user_entry: &UserData, layout: &BankLayout, table: &PendingImport) -> PendingBankImport {
+    let column = |name: &str| table.headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+    let date_column = column(layout.date);
+    let description_column = column(layout.description);
+    let category_column = layout.category.and_then(column);
+    let mcc_column = layout.mcc.and_then(column);
+    let status_column = layout.status.and_then(|(name, _)| column(name));
+    let (amount_column, signed) = match layout.amount {
+        AmountColumn::Signed(name) => (column(name), true),
+        AmountColumn::Debit(name) => (column(name), false),
+    };
+
+    let mut expenses = Vec::new();
+    let mut skipped_transfers = 0;
+    for row in &table.rows {
+        let cell = |column: Option<usize>| column.and_then(|column| row.get(column)).map(String::as_str).unwrap_or_default();
+
+        if let Some((_, ok_status)) = layout.status {
+            if !cell(status_column).eq_ignore_ascii_case(ok_status) {
+                continue;
+            }
+        }
+
+        // Поступления не импортируем: доходы записываются отдельно
+        let Some(amount) = parse_signed_amount(cell(amount_column)) else {
+            continue;
+        };
+        let amount = if signed { -amount } else { amount };
+        if amount <= 0.0 {
+            continue;
+        }
+
+        let Some((date, _)) = parse_import_date(cell(date_column), user_entry.timezone()) else {
+            continue;
+        };
+
+        let description = cell(description_column).to_string();
+        let bank_category = cell(category_column);
+        if is_internal_transfer(&description) || is_internal_transfer(bank_category) {
+            skipped_transfers += 1;
+            continue;
+        }
+
+        let mcc = cell(mcc_column).parse::<u16>().ok().or_else(|| find_mcc(&description));
+        let category = categorize_by_rules(user_entry, &description, mcc)
+            .or_else(|| user_entry.categories.iter().find(|category| category.to_lowercase() == bank_category.to_lowercase()).cloned())
+            .unwrap_or_else(|| DEFAULT_OTHER_CATEGORY.to_string());
+
+        expenses.push(Expense {
+            description,
+            amount,
+            category,
+            date,
+            tags: Vec::new(),
+            note: None,
+            attachment: None,
+            message_ids: Vec::new(),
+            account: None,
+            author: None,
+            split: Vec::new(),
+        });
+    }
+
+    PendingBankImport { bank: layout.name.to_string(), expenses, skipped_transfers }
+}
+
+pub async fn send_confirm_bank_import(
+    bot: Bot,
+    chat_id: ChatId,
+    user_entry: &UserData,
+    dialogue: MyDialogue,
+    pending_bank_import: PendingBankImport
+) -> HandlerResult {
+    let timezone = user_entry.timezone();
+    let check = DuplicateCheck::NearDate(Duration::days(BANK_DUPLICATE_WINDOW_DAYS));
+    let duplicates = find_duplicates(&user_entry.expenses, pending_bank_import.expenses.iter().map(|expense| (expense, check)))
+        .into_iter()
+        .filter(|duplicate| *duplicate)
+        .count();
+    let uncategorized = pending_bank_import.expenses.iter()
+        .filter(|expense| expense.category == DEFAULT_OTHER_CATEGORY)
+        .count();
+
+    let mut message = format!(
+        "Выписка {}\nТрат в выписке: {}\nБудет импортировано: {}\nУже записаны вручную: {}\nПропущено переводов между своими счетами: {}\n",
+        pending_bank_import.bank,
+        pending_bank_import.expenses.len(),
+        pending_bank_import.expenses.len() - duplicates,
+        duplicates,
+        pending_bank_import.skipped_transfers
+    );
+    if uncategorized > 0 {
+        message.push_str(&format!(
+            "Без подходящего правила ({} трат) попадут в '{}'. Правила настраиваются командой /rule\n",
+            uncategorized,
+            DEFAULT_OTHER_CATEGORY
+        ));
+    }
+
+    message.push_str("\nПервые траты:\n");
+    for expense in pending_bank_import.expenses.iter().take(IMPORT_PREVIEW_ROWS) {
+        message.push_str(&format!(
+            "[{}] {}: {}, {:.2}\n",
+            expense.date.with_timezone(&timezone).format("%d.%m.%y"),
+            expense.category,
+            expense.description,
+            expense.amount
+        ));
+    }
+    message.push_str("\nИмпортировать?");
+
+    let keyboard = KeyboardMarkup::new(
+        vec![vec![KeyboardButton::new("Да"), KeyboardButton::new("Нет")]])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    bot.send_message(chat_id, message).reply_markup(keyboard).await?;
+
+    info!("Changing state to ConfirmBankImport");
+    dialogue.update(State::ConfirmBankImport { pending_bank_import }).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_confirm_bank_import(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    pending_bank_import: PendingBankImport,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    if msg.text() != Some("Да") {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let check = DuplicateCheck::NearDate(Duration::days(BANK_DUPLICATE_WINDOW_DAYS));
+    let expenses = pending_bank_import.expenses.into_iter().map(|expense| (expense, check)).collect();
+    let (imported, duplicates) = import_expenses(user_entry, expenses, msg.from.as_ref().map(|user| user.id));
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, describe_import_result(imported, duplicates)).await?;
+    enter_default_state(bot, msg.chat.id, dialogue).await?;
+    Ok(())
+}
+
+fn is_internal_transfer(text: &str) -> bool {
+    let text = text.to_lowercase();
+    INTERNAL_TRANSFER_MARKERS.iter().any(|marker| text.contains(marker))
+}
+
+// Альфа-Банк пишет MCC прямо в описании: "... MCC5411"
+fn find_mcc(description: &str) -> Option<u16> {
+    let (_, rest) = description.split_once(MCC_MARKER)?;
+    let digits: String = rest.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse::<u16>().ok()
+}
@@ -0,0 +1,167 @@
+use crate::*;
+
+// Слова, с которых банки начинают уведомление о списании
+const PURCHASE_KEYWORDS: [&str; 3] = ["Покупка", "Оплата", "Списание"];
+const BALANCE_KEYWORDS: [&str; 3] = ["Баланс", "Доступно", "Остаток"];
+const CURRENCY_SUFFIXES: [&str; 5] = ["руб", "RUB", "RUR", "р", "₽"];
+const CARD_PREFIXES: [&str; 5] = ["*", "VISA", "MIR-", "ECMC", "MC"];
+const CARD_DIGITS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BankNotification {
+    pub amount: f64,
+    pub merchant: String,
+    // Последние цифры номера карты
+    pub card: Option<String>,
+    pub balance: Option<f64>,
+}
+
+impl BankNotification {
+    pub fn describe(&self) -> String {
+        let mut description = format!("Уведомление банка: покупка {:.2} в {}", self.amount, self.merchant);
+        if let Some(card) = &self.card {
+            description.push_str(&format!(", карта *{}", card));
+        }
+        if let Some(balance) = self.balance {
+            description.push_str(&format!(", баланс {:.2}", balance));
+        }
+        description
+    }
+
+    // Счёт, в названии которого есть цифры карты, иначе счёт по умолчанию
+    pub fn to_pending_expense(&self, user_entry: &UserData) -> PendingExpense {
+        let account = self.card.as_ref()
+            .and_then(|card| user_entry.accounts.iter().find(|account| account.name.ends_with(card.as_str())))
+            .map(|account| account.name.clone())
+            .or_else(|| user_entry.default_account.clone());
+
+        PendingExpense {
+            description: self.merchant.clone(),
+            amount: self.amount,
+            tags: Vec::new(),
+            message_id: None,
+            attachment: None,
+            date: None,
+            account,
+        }
+    }
+}
+
+// Чтобы поддержать новый банк, достаточно добавить сюда его разборщик
+const BANK_NOTIFICATION_PARSERS: [fn(&str) -> Option<BankNotification>; 2] = [
+    parse_dotted_notification,
+    parse_sms_notification,
+];
+
+pub fn parse_bank_notification(text: &str) -> Option<BankNotification> {
+    let text = text.replace('\u{a0}', " ");
+    BANK_NOTIFICATION_PARSERS.iter().find_map(|parser| parser(text.trim()))
+}
+
+// Т-Банк и Альфа-Банк делят уведомление на части точками:
+// "Покупка, карта *1234. 1530 RUB. PYATEROCHKA. Доступно 10000 RUB"
+fn parse_dotted_notification(text: &str) -> Option<BankNotification> {
+    let parts: Vec<&str> = text.split(". ").map(|part| part.trim().trim_end_matches('.')).collect();
+    let [operation, amount, merchant, rest @ ..] = parts.as_slice() else {
+        return None;
+    };
+    if !starts_with_purchase(operation) {
+        return None;
+    }
+
+    let (amount, tail) = parse_money(amount)?;
+    if !tail.trim().is_empty() || merchant.is_empty() {
+        return None;
+    }
+
+    Some(BankNotification {
+        amount,
+        merchant: merchant.to_string(),
+        card: find_card(operation),
+        balance: rest.iter().find_map(|part| parse_balance(part)),
+    })
+}
+
+// Сбербанк присылает всё одной строкой:
+// "Покупка 1 530р Карта*1234 PYATEROCHKA Баланс: 10 000р" или "MIR-1234 19:45 Покупка 1530р PYATEROCHKA Баланс: 10000р"
+fn parse_sms_notification(text: &str) -> Option<BankNotification> {
+    let (prefix, keyword, rest) = PURCHASE_KEYWORDS.iter()
+        .find_map(|keyword| text.find(keyword).map(|pos| (&text[..pos], keyword, &text[pos + keyword.len()..])))?;
+    let (amount, rest) = parse_money(rest)?;
+
+    let (merchant_part, balance_part) = match BALANCE_KEYWORDS.iter().find_map(|keyword| rest.find(keyword)) {
+        Some(pos) => (&rest[..pos], Some(&rest[pos..])),
+        None => (rest, None),
+    };
+
+    // Номер карты может стоять и перед словом "Покупка", и после суммы
+    let card = find_card(merchant_part).or_else(|| find_card(prefix));
+    let merchant = merchant_part.split_whitespace()
+        .filter(|word| !is_card_word(word) && !word.eq_ignore_ascii_case("карта"))
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if merchant.is_empty() {
+        info!("Bank notification '{}' has no merchant", keyword);
+        return None;
+    }
+
+    Some(BankNotification {
+        amount,
+        merchant,
+        card,
+        balance: balance_part.and_then(parse_balance),
+    })
+}
+
+fn starts_with_purchase(text: &str) -> bool {
+    PURCHASE_KEYWORDS.iter().any(|keyword| text.starts_with(keyword))
+}
+
+fn parse_balance(text: &str) -> Option<f64> {
+    let keyword = BALANCE_KEYWORDS.iter().find(|keyword| text.starts_with(*keyword))?;
+    let rest = text[keyword.len()..].trim_start_matches([':', ' ']);
+    parse_money(rest).map(|(amount, _)| amount)
+}
+
+// Сумма с разделителями разрядов и валютой: "1 530,50р", "1530 RUB". Возвращает сумму и остаток строки
+fn parse_money(text: &str) -> Option<(f64, &str)> {
+    let text = text.trim_start();
+    let mut number = String::new();
+    let mut end = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let next_is_digit = chars.peek().is_some_and(|(_, next)| next.is_ascii_digit());
+        match c {
+            '0'..='9' => number.push(c),
+            ',' | '.' if next_is_digit && !number.is_empty() => number.push('.'),
+            ' ' if next_is_digit && !number.is_empty() => {}
+            _ => break,
+        }
+        end = pos + c.len_utf8();
+    }
+
+    let rest = text[end..].trim_start();
+    let currency = CURRENCY_SUFFIXES.iter().find(|currency| rest.starts_with(*currency))?;
+    let amount = number.parse::<f64>().ok().filter(|amount| amount.is_finite() && *amount > 0.0)?;
+    Some((amount, &rest[currency.len()..]))
+}
+
+fn find_card(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .flat_map(|word| word.split(','))
+        .find_map(card_digits)
+}
+
+fn is_card_word(word: &str) -> bool {
+    card_digits(word).is_some()
+}
+
+// "*1234", "Карта*1234", "VISA1234", "MIR-1234"
+fn card_digits(word: &str) -> Option<String> {
+    let word = word.trim_start_matches("Карта").trim_start_matches("карта");
+    let digits = CARD_PREFIXES.iter().find_map(|prefix| word.strip_prefix(prefix))?;
+    if digits.len() != CARD_DIGITS || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(digits.to_string())
+}
@@ -9,10 +9,10 @@ pub enum State {
     Default,
     AddExpense,
     SelectCategory {
-        pending_expense: (String, f64),
+        pending_expense: PendingExpense,
     },
     ConfirmAddExpense {
-        pending_expense: (String, f64),
+        pending_expense: PendingExpense,
         category: String,
     },
     AddCategory,
@@ -25,6 +25,34 @@ pub enum State {
     },
     CleanupExpenses,
     ConfirmCleanupExpenses,
+    AddIncome,
+    SelectIncomeCategory {
+        pending_income: PendingIncome,
+    },
+    ConfirmAddIncome {
+        pending_income: PendingIncome,
+        category: String,
+    },
+    AddRecurring,
+    SelectRecurringCategory {
+        pending_recurring: PendingRecurring,
+    },
+    ConfirmRecurring {
+        pending_recurring: PendingRecurring,
+    },
+    SelectImportColumn {
+        pending_import: PendingImport,
+        field: ImportField,
+    },
+    ConfirmImport {
+        pending_import: PendingImport,
+    },
+    ConfirmBankImport {
+        pending_bank_import: PendingBankImport,
+    },
+    ConfirmImportAccounts {
+        pending_interchange_import: PendingInterchangeImport,
+    },
 }
 
 #[serde_with::serde_as]
@@ -35,21 +63,392 @@ pub struct Expense {
     pub category: String,
     #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
     pub date: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    // Сообщения, ответом на которые можно дополнить трату: исходное и подтверждение добавления.
+    // Id сообщений уникальны только внутри чата, а в общий бюджет пишут из группы и из личных чатов
+    #[serde(default)]
+    pub message_ids: Vec<(ChatId, MessageId)>,
+    #[serde(default)]
+    pub account: Option<String>,
+    // Кто записал трату, важно для общих бюджетов
+    #[serde(default)]
+    pub author: Option<UserId>,
+    // Доли участников, если трата разделена. Оплатил её автор
+    #[serde(default)]
+    pub split: Vec<SplitShare>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SplitShare {
+    pub member: UserId,
+    pub amount: f64,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settlement {
+    pub from: UserId,
+    pub to: UserId,
+    pub amount: f64,
+    #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentKind {
+    Photo,
+    Document,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attachment {
+    pub file_id: String,
+    pub kind: AttachmentKind,
+    pub local_path: Option<String>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Income {
+    pub description: String,
+    pub amount: f64,
+    pub category: String,
+    #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
+    pub date: DateTime<Utc>,
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingIncome {
+    pub description: String,
+    pub amount: f64,
+    pub account: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RecurringSchedule {
+    Monthly { day: u32 },
+    Yearly { month: u32, day: u32 },
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecurringExpense {
+    pub id: u64,
+    pub description: String,
+    pub amount: f64,
+    pub category: String,
+    pub tags: Vec<String>,
+    pub account: Option<String>,
+    pub schedule: RecurringSchedule,
+    #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
+    pub next_date: DateTime<Utc>,
+    // Спрашивать подтверждение перед добавлением траты вместо автоматического добавления
+    pub confirm: bool,
+    pub awaiting_confirmation: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingRecurring {
+    pub pending_expense: PendingExpense,
+    pub schedule: RecurringSchedule,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportField {
+    Date,
+    Amount,
+    Description,
+    Category,
+}
+
+// Загруженная таблица и выбранные пользователем столбцы
+#[derive(Debug, Clone, Default)]
+pub struct PendingImport {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub date_column: Option<usize>,
+    pub amount_column: Option<usize>,
+    pub description_column: Option<usize>,
+    pub category_column: Option<usize>,
+}
+
+// Траты из банковской выписки, уже разобранные и разнесённые по категориям
+#[derive(Debug, Clone)]
+pub struct PendingBankImport {
+    pub bank: String,
+    pub expenses: Vec<Expense>,
+    pub skipped_transfers: usize,
+}
+
+// Операции из OFX или QIF, в которых есть счета, которых у пользователя ещё нет
+#[derive(Debug, Clone)]
+pub struct PendingInterchangeImport {
+    pub transactions: Vec<ImportedTransaction>,
+    pub new_accounts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RuleCondition {
+    // Подстрока описания операции без учёта регистра
+    Keyword(String),
+    Mcc(u16),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CategoryRule {
+    pub condition: RuleCondition,
+    pub category: String,
+}
+
+// Месячный лимит трат для категории (вместе с подкатегориями) или для всех трат, если категория не указана
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Budget {
+    pub category: Option<String>,
+    pub limit: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ContributionSource {
+    Manual,
+    Income,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Contribution {
+    pub amount: f64,
+    #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
+    pub date: DateTime<Utc>,
+    pub source: ContributionSource,
+    // Номер дохода, часть которого отложена кнопкой, чтобы повторное нажатие не засчитало её дважды
+    #[serde(default)]
+    pub income_index: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Goal {
+    pub name: String,
+    pub target: f64,
+    pub deadline: NaiveDate,
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DigestFrequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DigestSettings {
+    pub frequency: DigestFrequency,
+    // Местное время отправки сводки
+    pub time: NaiveTime,
+    pub last_sent: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReminderSettings {
+    // Местное время, после которого напоминаем, если за день нет трат
+    pub time: NaiveTime,
+    pub last_sent: Option<NaiveDate>,
+    pub snoozed_until: Option<DateTime<Utc>>,
+    // День, отмеченный кнопкой "Сегодня ничего не тратил"
+    pub nothing_spent: Option<NaiveDate>,
+}
+
+// Порядок важен: каждая следующая роль может всё, что предыдущая
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Role {
+    Viewer,
+    #[default]
+    Editor,
+    Owner,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LedgerMember {
+    pub id: UserId,
+    pub name: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+// Одноразовая ссылка-приглашение в бюджет: t.me/<бот>?start=<token>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Invite {
+    pub token: String,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Account {
+    pub name: String,
+    pub initial_balance: f64,
+}
+
+// Перевод между своими счетами, в тратах и доходах не учитывается
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Transfer {
+    pub from: String,
+    pub to: String,
+    pub amount: f64,
+    #[serde_as(as = "serde_with::TimestampSecondsWithFrac<String>")]
+    pub date: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PendingExpense {
+    pub description: String,
+    pub amount: f64,
+    pub tags: Vec<String>,
+    pub message_id: Option<MessageId>,
+    pub attachment: Option<Attachment>,
+    // Время покупки, если оно известно заранее (например, из QR-кода чека)
+    pub date: Option<DateTime<Utc>>,
+    pub account: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserData {
     pub expenses: Vec<Expense>,
     pub categories: Vec<String>,
     pub requested_clear: bool,
     pub pending_expense: Option<(String, f64)>,
+    #[serde(default = "default_utc_offset_minutes")]
+    pub utc_offset_minutes: i32,
+    #[serde(default)]
+    pub incomes: Vec<Income>,
+    #[serde(default)]
+    pub income_categories: Vec<String>,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub default_account: Option<String>,
+    #[serde(default)]
+    pub transfers: Vec<Transfer>,
+    #[serde(default)]
+    pub recurring_expenses: Vec<RecurringExpense>,
+    #[serde(default)]
+    pub next_recurring_id: u64,
+    #[serde(default)]
+    pub budgets: Vec<Budget>,
+    #[serde(default = "default_budget_thresholds")]
+    pub budget_thresholds: Vec<u32>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub digest: Option<DigestSettings>,
+    #[serde(default)]
+    pub reminder: Option<ReminderSettings>,
+    #[serde(default)]
+    pub category_rules: Vec<CategoryRule>,
+    // Название группового чата, которому принадлежит бюджет
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub members: Vec<LedgerMember>,
+    // Общий бюджет, с которым сейчас работает личный чат
+    #[serde(default)]
+    pub linked_ledger: Option<ChatId>,
+    #[serde(default)]
+    pub settlements: Vec<Settlement>,
+    #[serde(default)]
+    pub invites: Vec<Invite>,
+    // Удалённые из группового бюджета участники: вернуться они могут только по новому приглашению
+    #[serde(default)]
+    pub removed: Vec<UserId>,
+}
+
+impl Default for UserData {
+    fn default() -> Self {
+        UserData {
+            expenses: Vec::new(),
+            categories: Vec::new(),
+            requested_clear: false,
+            pending_expense: None,
+            utc_offset_minutes: DEFAULT_UTC_OFFSET_MINUTES,
+            incomes: Vec::new(),
+            income_categories: Vec::new(),
+            accounts: Vec::new(),
+            default_account: None,
+            transfers: Vec::new(),
+            recurring_expenses: Vec::new(),
+            next_recurring_id: 0,
+            budgets: Vec::new(),
+            budget_thresholds: default_budget_thresholds(),
+            goals: Vec::new(),
+            digest: None,
+            reminder: None,
+            category_rules: Vec::new(),
+            title: None,
+            members: Vec::new(),
+            linked_ledger: None,
+            settlements: Vec::new(),
+            invites: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl UserData {
+    pub fn timezone(&self) -> FixedOffset {
+        FixedOffset::east_opt(self.utc_offset_minutes * 60)
+            .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+fn default_utc_offset_minutes() -> i32 {
+    DEFAULT_UTC_OFFSET_MINUTES
+}
+
+fn default_budget_thresholds() -> Vec<u32> {
+    DEFAULT_BUDGET_THRESHOLDS.to_vec()
+}
+
+// Заблокированные и одобренные администратором пользователи, хранятся отдельно от бюджетов
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AccessData {
+    #[serde(default)]
+    pub blocked: Vec<UserId>,
+    #[serde(default)]
+    pub allowed: Vec<UserId>,
+}
+
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase", description = "Команды администратора")]
+pub enum AdminCommand {
+    #[command(description = "Число пользователей и размер хранилища")]
+    Stats,
+    #[command(description = "Отправить сообщение всем пользователям: /broadcast текст")]
+    Broadcast(String),
+    #[command(description = "Заблокировать пользователя: /block id")]
+    Block(String),
+    #[command(description = "Разблокировать пользователя: /unblock id")]
+    Unblock(String),
+    #[command(description = "Разрешить пользователю доступ в режиме списка: /allow id")]
+    Allow(String),
+    #[command(description = "Убрать пользователя из списка разрешённых: /disallow id")]
+    Disallow(String),
+    #[command(description = "Показать команды администратора")]
+    AdminHelp,
 }
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Доступные команды")]
 pub enum Command {
     #[command(description = "Показать приветственное сообщение")]
-    Start,
+    Start(String),
     #[command(description = "Показать это сообщение")]
     Help,
     #[command(description = "Добавить трату")]
@@ -62,10 +461,74 @@ pub enum Command {
     DeleteCategory,
     #[command(description = "Удалить все траты")]
     ClearAllExpenses,
-    #[command(description = "Вывести список всех трат")]
-    AllExpenses,
-    #[command(description = "Вывести сумму трат")]
-    TotalExpenses,
-    #[command(description = "Вывести сумму трат по категориям")]
-    ExpensesByCategory,
+    #[command(description = "Вывести список всех трат, можно отфильтровать по тегам, счёту и периоду: /allexpenses #отпуск @карта месяц")]
+    AllExpenses(String),
+    #[command(description = "Вывести сумму трат, можно отфильтровать по тегам, счёту и периоду")]
+    TotalExpenses(String),
+    #[command(description = "Вывести сумму трат по категориям, можно отфильтровать по тегам, счёту и периоду")]
+    ExpensesByCategory(String),
+    #[command(description = "Вывести сумму трат по тегам")]
+    ExpensesByTag(String),
+    #[command(description = "Добавить доход, или напишите его в формате: +зарплата 150000")]
+    AddIncome,
+    #[command(description = "Вывести список доходов, можно указать период: /incomes месяц")]
+    Incomes(String),
+    #[command(description = "Вывести доходы, расходы и баланс за период: /balance месяц")]
+    Balance(String),
+    #[command(description = "Вывести счета и их балансы")]
+    Accounts,
+    #[command(description = "Добавить счёт: /addaccount карта 15000")]
+    AddAccount(String),
+    #[command(description = "Выбрать счёт по умолчанию: /defaultaccount карта")]
+    DefaultAccount(String),
+    #[command(description = "Перевести деньги между счетами: /transfer карта наличные 5000")]
+    Transfer(String),
+    #[command(description = "Вывести регулярные траты и ближайшие списания")]
+    Recurring,
+    #[command(description = "Добавить регулярную трату: /addrecurring аренда 30000 ежемесячно 5")]
+    AddRecurring(String),
+    #[command(description = "Удалить регулярную трату по её номеру: /deleterecurring 2")]
+    DeleteRecurring(String),
+    #[command(description = "Установить месячный бюджет категории или общий: /budget Еда 30000, /budget 100000")]
+    Budget(String),
+    #[command(description = "Вывести бюджеты и траты за текущий месяц")]
+    Budgets,
+    #[command(description = "Настроить пороги предупреждений о бюджете в процентах: /budgetalerts 80 100")]
+    BudgetAlerts(String),
+    #[command(description = "Добавить цель накоплений: /addgoal Отпуск 200000 31.12.2026")]
+    AddGoal(String),
+    #[command(description = "Вывести цели накоплений и прогноз их достижения")]
+    Goals,
+    #[command(description = "Отложить деньги на цель: /contribute Отпуск 5000")]
+    Contribute(String),
+    #[command(description = "Вывести сводку за период: /summary неделя")]
+    Summary(String),
+    #[command(description = "Настроить регулярную сводку: /digest ежедневно 21:00, /digest еженедельно 20:00, /digest ежемесячно 20:00, /digest выкл")]
+    Digest(String),
+    #[command(description = "Напоминать о записи трат: /reminder 21:00, /reminder выкл")]
+    Reminder(String),
+    #[command(description = "Выгрузить траты в CSV, Excel, JSON, OFX или QIF: /export 09.2026, /export месяц #отпуск. Файл JSON можно загрузить обратно")]
+    Export(String),
+    #[command(description = "Правило категории для импорта выписок: /rule пятёрочка Еда/Продукты, /rule mcc:5812 Еда/Рестораны")]
+    Rule(String),
+    #[command(description = "Показать правила категорий")]
+    Rules,
+    #[command(description = "Удалить правило категории по его номеру: /deleterule 2")]
+    DeleteRule(String),
+    #[command(description = "Общие бюджеты: /ledger - список, /ledger 1 - работать с бюджетом из лички, /ledger личный - вернуться к своему")]
+    Ledger(String),
+    #[command(description = "Вывести траты по участникам общего бюджета: /bymember месяц")]
+    ByMember(String),
+    #[command(description = "Разделить трату между участниками: /split 12 - поровну на всех, /split 12 0 2 - поровну на участников 0 и 2, /split 12 0:2 1:1 - по долям, /split 12 0=1500 1=500 - точными суммами")]
+    Split(String),
+    #[command(description = "Показать, кто кому должен, и рассчитаться")]
+    Settle,
+    #[command(description = "Пригласить в бюджет по ссылке: /invite редактор или /invite наблюдатель")]
+    Invite(String),
+    #[command(description = "Изменить роль участника из /ledger: /role 2 наблюдатель, /role 2 удалить")]
+    Role(String),
+    #[command(description = "Показать заметку и вложение траты по её номеру: /attachment 3")]
+    Attachment(String),
+    #[command(description = "Показать или изменить часовой пояс: /timezone +3")]
+    Timezone(String),
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+use crate::*;
+
+const MAX_BUDGET_THRESHOLD: u32 = 1000;
+
+pub async fn handle_budget_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /budget");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let limit = words.last().and_then(|word| word.parse::<f64>().ok()).filter(|limit| limit.is_finite() && *limit >= 0.0);
+    let Some(limit) = limit else {
+        bot.send_message(msg.chat.id,
+            "Укажите категорию и месячный лимит, например: /budget Еда 30000. Общий бюджет: /budget 100000. Чтобы убрать бюджет, укажите 0")
+            .await?;
+        return Ok(());
+    };
+
+    let category = normalize_category(&words[..words.len() - 1].join(" "));
+    let category = if category.is_empty() { None } else { Some(category) };
+    let name = budget_name(&category);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    user_entry.budgets.retain(|budget| budget.category != category);
+    let message = if limit == 0.0 {
+        format!("{} удалён", name)
+    } else {
+        user_entry.budgets.push(Budget { category, limit });
+        format!("{}: {:.2} в месяц", name, limit)
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_budgets(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.budgets.is_empty() {
+        bot.send_message(msg.chat.id, "У вас нет бюджетов. Установите их командой /budget, например: /budget Еда 30000").await?;
+        return Ok(());
+    }
+
+    let (start, end) = Period::Month.range(user_entry.timezone(), Utc::now());
+    let mut message = format!("Бюджеты {}:\n\n", Period::Month.label());
+    for budget in &user_entry.budgets {
+        let spent = budget_spent(user_entry, budget, start, end);
+        message.push_str(&format!(
+            "{}: {:.2} из {:.2} ({:.0}%), {}\n",
+            budget_name(&budget.category),
+            spent,
+            budget.limit,
+            spent / budget.limit * 100.0,
+            describe_remaining(budget.limit - spent)
+        ));
+    }
+    message.push_str(&format!(
+        "\nПредупреждения при достижении: {}",
+        format_thresholds(&user_entry.budget_thresholds)
+    ));
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_budget_alerts_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /budgetalerts");
+    let thresholds: Option<Vec<u32>> = args.split_whitespace()
+        .map(|word| word.trim_end_matches('%').parse::<u32>().ok().filter(|t| (1..=MAX_BUDGET_THRESHOLD).contains(t)))
+        .collect();
+
+    let Some(mut thresholds) = thresholds.filter(|thresholds| !thresholds.is_empty()) else {
+        bot.send_message(msg.chat.id, "Укажите пороги в процентах через пробел, например: /budgetalerts 80 100").await?;
+        return Ok(());
+    };
+    thresholds.sort();
+    thresholds.dedup();
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let message = format!("Предупреждения о бюджете будут приходить при достижении: {}", format_thresholds(&thresholds));
+    user_entry.budget_thresholds = thresholds;
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+// Остаток бюджетов, затронутых новой тратой, и предупреждения о пересечённых порогах.
+// Вызывается до того, как трата добавлена в user_entry
+pub fn budget_report_after_expense(user_entry: &UserData, expense: &Expense) -> Option<String> {
+    let (start, end) = Period::Month.range(user_entry.timezone(), Utc::now());
+    if expense.date < start || expense.date >= end {
+        return None;
+    }
+
+    let mut lines = Vec::new();
+    for budget in user_entry.budgets.iter().filter(|budget| budget_includes(budget, expense)) {
+        let spent_before = budget_spent(user_entry, budget, start, end);
+        let spent = spent_before + expense.amount;
+        lines.push(format!("{}: {}", budget_name(&budget.category), describe_remaining(budget.limit - spent)));
+
+        let crossed = user_entry.budget_thresholds.iter()
+            .filter(|threshold| {
+                let amount = budget.limit * **threshold as f64 / 100.0;
+                spent_before < amount && amount <= spent
+            })
+            .max();
+        if let Some(threshold) = crossed {
+            lines.push(format!("⚠️ Потрачено {}% бюджета ({:.2} из {:.2})", threshold, spent, budget.limit));
+        }
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+    Some(lines.join("\n"))
+}
+
+fn budget_includes(budget: &Budget, expense: &Expense) -> bool {
+    budget.category.as_ref().is_none_or(|category| is_subcategory_of(&expense.category, category))
+}
+
+fn budget_spent(user_entry: &UserData, budget: &Budget, start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    user_entry.expenses.iter()
+        .filter(|expense| start <= expense.date && expense.date < end && budget_includes(budget, expense))
+        .map(|expense| expense.amount)
+        .sum()
+}
+
+fn budget_name(category: &Option<String>) -> String {
+    match category {
+        Some(category) => format!("Бюджет категории '{}'", category),
+        None => String::from("Общий бюджет"),
+    }
+}
+
+fn describe_remaining(remaining: f64) -> String {
+    if remaining >= 0.0 {
+        format!("осталось {:.2}", remaining)
+    } else {
+        format!("превышен на {:.2}", -remaining)
+    }
+}
+
+fn format_thresholds(thresholds: &[u32]) -> String {
+    thresholds.iter()
+        .map(|threshold| format!("{}%", threshold))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
@@ -0,0 +1,57 @@
+use crate::*;
+
+pub const CATEGORY_SEPARATOR: char = '/';
+
+// Приводит "Еда / Рестораны" к виду "Еда/Рестораны", пустые уровни отбрасываются
+pub fn normalize_category(text: &str) -> String {
+    text.split(CATEGORY_SEPARATOR)
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(&CATEGORY_SEPARATOR.to_string())
+}
+
+pub fn parent_category(category: &str) -> Option<&str> {
+    category.rsplit_once(CATEGORY_SEPARATOR).map(|(parent, _)| parent)
+}
+
+pub fn category_name(category: &str) -> &str {
+    category.rsplit_once(CATEGORY_SEPARATOR).map_or(category, |(_, name)| name)
+}
+
+pub fn is_subcategory_of(category: &str, parent: &str) -> bool {
+    category == parent
+        || (category.starts_with(parent) && category[parent.len()..].starts_with(CATEGORY_SEPARATOR))
+}
+
+// Возвращает категорию следующего уровня под parent, в которую входит category.
+// Для parent = None это категория верхнего уровня
+pub fn child_category_of(category: &str, parent: Option<&str>) -> Option<String> {
+    let rest = match parent {
+        Some(parent) if category == parent => return Some(parent.to_string()),
+        Some(parent) if is_subcategory_of(category, parent) => &category[parent.len() + 1..],
+        Some(_) => return None,
+        None => category,
+    };
+
+    let name = rest.split(CATEGORY_SEPARATOR).next().unwrap_or(rest);
+    Some(match parent {
+        Some(parent) => format!("{}{}{}", parent, CATEGORY_SEPARATOR, name),
+        None => name.to_string(),
+    })
+}
+
+// Добавляет категорию вместе со всеми родительскими, которых ещё нет в списке
+pub fn add_category_with_parents(user_entry: &mut UserData, category: &str) {
+    let mut path = String::new();
+    for part in category.split(CATEGORY_SEPARATOR) {
+        if !path.is_empty() {
+            path.push(CATEGORY_SEPARATOR);
+        }
+        path.push_str(part);
+
+        if !user_entry.categories.contains(&path) {
+            user_entry.categories.push(path.clone());
+        }
+    }
+}
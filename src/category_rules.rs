@@ -0,0 +1,112 @@
+use crate::*;
+
+const MCC_PREFIX: &str = "mcc:";
+
+pub async fn handle_rule_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /rule");
+    let args = args.trim();
+    let parsed = args.split_once(' ')
+        .and_then(|(condition, category)| Some((parse_rule_condition(condition)?, normalize_category(category))))
+        .filter(|(_, category)| !category.is_empty());
+    let Some((condition, category)) = parsed else {
+        bot.send_message(msg.chat.id,
+            "Укажите слово из описания операции или код MCC и категорию, например: /rule пятёрочка Еда/Продукты или /rule mcc:5812 Еда/Рестораны")
+            .await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let message = format!("Правило добавлено: {} → {}", describe_rule_condition(&condition), category);
+    user_entry.category_rules.retain(|rule| rule.condition != condition);
+    add_category_with_parents(user_entry, &category);
+    user_entry.category_rules.push(CategoryRule { condition, category });
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_category_rules(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.category_rules.is_empty() {
+        bot.send_message(msg.chat.id, "У вас нет правил категорий. Добавьте их командой /rule, например: /rule пятёрочка Еда/Продукты").await?;
+        return Ok(());
+    }
+
+    let mut message = String::from("Правила категорий для импорта выписок:\n\n");
+    for (i, rule) in user_entry.category_rules.iter().enumerate() {
+        message.push_str(&format!("{}. {} → {}\n", i, describe_rule_condition(&rule.condition), rule.category));
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_delete_rule_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let Ok(id) = args.trim().parse::<usize>() else {
+        bot.send_message(msg.chat.id, "Укажите номер правила из /rules, например: /deleterule 2").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if id >= user_entry.category_rules.len() {
+        bot.send_message(msg.chat.id, "Нет правила с таким номером").await?;
+        return Ok(());
+    }
+
+    let rule = user_entry.category_rules.remove(id);
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!("Правило '{}' удалено", describe_rule_condition(&rule.condition))).await?;
+    Ok(())
+}
+
+// Правила по MCC точнее слов из описания, поэтому проверяются первыми
+pub fn categorize_by_rules(user_entry: &UserData, description: &str, mcc: Option<u16>) -> Option<String> {
+    let description = description.to_lowercase();
+    let by_mcc = mcc.and_then(|mcc| user_entry.category_rules.iter().find(|rule| rule.condition == RuleCondition::Mcc(mcc)));
+    let by_keyword = || user_entry.category_rules.iter()
+        .find(|rule| matches!(&rule.condition, RuleCondition::Keyword(keyword) if description.contains(keyword.as_str())));
+
+    by_mcc.or_else(by_keyword).map(|rule| rule.category.clone())
+}
+
+fn parse_rule_condition(word: &str) -> Option<RuleCondition> {
+    let word = word.to_lowercase();
+    match word.strip_prefix(MCC_PREFIX) {
+        Some(mcc) => mcc.parse::<u16>().ok().map(RuleCondition::Mcc),
+        None => Some(RuleCondition::Keyword(word)),
+    }
+}
+
+fn describe_rule_condition(condition: &RuleCondition) -> String {
+    match condition {
+        RuleCondition::Keyword(keyword) => format!("'{}'", keyword),
+        RuleCondition::Mcc(mcc) => format!("MCC {:04}", mcc),
+    }
+}
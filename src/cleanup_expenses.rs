@@ -10,7 +10,7 @@ pub async fn handle_message_on_confirm_cleanup_expenses(
     bot: Bot,
     msg: Message,
     dialogue: MyDialogue,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     let text = if let Some(text) = msg.text() {
         text.to_owned()
@@ -25,9 +25,8 @@ pub async fn handle_message_on_confirm_cleanup_expenses(
     }
 
     if text == "Да" {
-        let user_id = msg.from.as_ref().unwrap().id;
         let mut data = user_data.lock().await;
-        let user_entry = get_user_entry(&mut data, user_id);
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
         
         user_entry.expenses.clear();
 
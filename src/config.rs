@@ -0,0 +1,99 @@
+use crate::*;
+
+const ATTACHMENTS_DIR_VAR: &str = "ATTACHMENTS_DIR";
+const ADMIN_IDS_VAR: &str = "ADMIN_IDS";
+const ALLOWLIST_VAR: &str = "ALLOWLIST";
+const WEBHOOK_URL_VAR: &str = "WEBHOOK_URL";
+const WEBHOOK_ADDRESS_VAR: &str = "WEBHOOK_ADDRESS";
+const WEBHOOK_SECRET_VAR: &str = "WEBHOOK_SECRET";
+const WEBHOOK_SKIP_SETUP_VAR: &str = "WEBHOOK_SKIP_SETUP";
+const METRICS_ADDRESS_VAR: &str = "METRICS_ADDRESS";
+const DEFAULT_WEBHOOK_ADDRESS: &str = "0.0.0.0:8443";
+
+// Настройки бота из переменных окружения (и файла .env, если он есть)
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    // Если задано, вложения к тратам дополнительно скачиваются в эту папку
+    pub attachments_dir: Option<PathBuf>,
+    // Id администраторов через запятую: ADMIN_IDS=123,456
+    pub admin_ids: Vec<UserId>,
+    // ALLOWLIST=1 - ботом могут пользоваться только одобренные администратором
+    pub allowlist: bool,
+    // Если задан WEBHOOK_URL, бот получает обновления через вебхук, иначе через long polling
+    pub webhook: Option<WebhookConfig>,
+    // Отдельный адрес для /healthz и /metrics. Если не задан, их отдаёт сервер вебхука
+    pub metrics_address: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    // Адрес, на котором слушает встроенный HTTP-сервер
+    pub address: SocketAddr,
+    // Публичный адрес за reverse proxy, который сообщается Telegram
+    pub url: Url,
+    // Telegram присылает его в заголовке X-Telegram-Bot-Api-Secret-Token, запросы без него отклоняются
+    pub secret_token: Option<String>,
+    // WEBHOOK_SKIP_SETUP=1 - не вызывать setWebhook, для локальной проверки запросами с примерами Update
+    pub skip_setup: bool,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        if dotenv::dotenv().is_ok() {
+            info!("Loaded .env file");
+        }
+
+        Config {
+            attachments_dir: env::var(ATTACHMENTS_DIR_VAR).ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            admin_ids: env::var(ADMIN_IDS_VAR).unwrap_or_default()
+                .split(',')
+                .filter_map(|id| id.trim().parse::<u64>().ok())
+                .map(UserId)
+                .collect(),
+            allowlist: env::var(ALLOWLIST_VAR).is_ok_and(|value| is_enabled(&value)),
+            webhook: webhook_from_env(),
+            metrics_address: env::var(METRICS_ADDRESS_VAR).ok()
+                .filter(|address| !address.is_empty())
+                .and_then(|address| address.parse::<SocketAddr>()
+                    .inspect_err(|e| warn!("Invalid {}: {}", METRICS_ADDRESS_VAR, e))
+                    .ok()),
+        }
+    }
+
+    pub fn is_admin(&self, user_id: UserId) -> bool {
+        self.admin_ids.contains(&user_id)
+    }
+}
+
+fn webhook_from_env() -> Option<WebhookConfig> {
+    let url = env::var(WEBHOOK_URL_VAR).ok().filter(|url| !url.is_empty())?;
+    let url = match Url::parse(&url) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!("Invalid {}, using long polling: {}", WEBHOOK_URL_VAR, e);
+            return None;
+        }
+    };
+
+    let address = env::var(WEBHOOK_ADDRESS_VAR).unwrap_or_else(|_| DEFAULT_WEBHOOK_ADDRESS.to_string());
+    let address = match address.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(e) => {
+            warn!("Invalid {}, using long polling: {}", WEBHOOK_ADDRESS_VAR, e);
+            return None;
+        }
+    };
+
+    Some(WebhookConfig {
+        address,
+        url,
+        secret_token: env::var(WEBHOOK_SECRET_VAR).ok().filter(|secret| !secret.is_empty()),
+        skip_setup: env::var(WEBHOOK_SKIP_SETUP_VAR).is_ok_and(|value| is_enabled(&value)),
+    })
+}
+
+fn is_enabled(value: &str) -> bool {
+    ["1", "true", "yes"].contains(&value.to_lowercase().as_str())
+}
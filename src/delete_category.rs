@@ -1,16 +1,15 @@
 use crate::*;
 
-pub async fn start_delete_category(bot: Bot, msg: Message, dialogue: MyDialogue, user_data: Arc<Mutex<HashMap<UserId, UserData>>>) -> HandlerResult {
+pub async fn start_delete_category(bot: Bot, msg: Message, dialogue: MyDialogue, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) -> HandlerResult {
     info!("Got command /deletecategory");
-    let user_id = msg.from.as_ref().unwrap().id;
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
     send_delete_category(bot, msg.chat.id, dialogue, user_entry).await?;
     Ok(())
 }
 
 pub async fn handle_message_on_delete_category(
-    bot: Bot, msg: Message, dialogue: MyDialogue, user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    bot: Bot, msg: Message, dialogue: MyDialogue, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     let text = if let Some(text) = msg.text() {
         text.to_owned()
@@ -24,9 +23,9 @@ pub async fn handle_message_on_delete_category(
         return Ok(());
     }
 
-    let user_id = msg.from.as_ref().unwrap().id;
+    let text = normalize_category(&text);
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if let Ok(id) = text.parse::<usize>() {
         if id >= user_entry.categories.len() {
@@ -67,7 +66,7 @@ pub async fn handle_message_on_confirm_delete_category(
     msg: Message,
     dialogue: MyDialogue,
     category: String,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
     let text = if let Some(text) = msg.text() {
         text.to_owned()
@@ -82,35 +81,30 @@ pub async fn handle_message_on_confirm_delete_category(
     }
 
     if text == "Да" {
-        let user_id = msg.from.as_ref().unwrap().id;
         let mut data = user_data.lock().await;
-        let user_entry = get_user_entry(&mut data, user_id);
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
         
-        if let Some(pos) = user_entry.categories.iter().position(|c| c == &category) {
-            user_entry.categories.remove(pos);
-        }
+        let target_category = deleted_category_target(&category).to_string();
+        user_entry.categories.retain(|c| !is_subcategory_of(c, &category));
 
-        let default_category = DEFAULT_OTHER_CATEGORY.to_string();
         let mut was_expenses = false;
         for expense in &mut user_entry.expenses {
-            if expense.category == category {
+            if is_subcategory_of(&expense.category, &category) {
                 was_expenses = true;
-                expense.category = default_category.clone();
+                expense.category = target_category.clone();
             }
         }
 
-        if !user_entry.categories.contains(&default_category) {
-            user_entry.categories.push(default_category);
-        }
+        add_category_with_parents(user_entry, &target_category);
 
         if let Err(e) = save_user_data(&data).await {
             warn!("Save data error: {}", e);
         }
 
         let message = if was_expenses {
-            "Категория успешно удалена, все траты перемещены в категорию 'Другое'"
+            format!("Категория успешно удалена, все траты перемещены в категорию '{}'", target_category)
         } else {
-            "Категория успешно удалена, трат в этой категории не было"
+            String::from("Категория успешно удалена, трат в этой категории не было")
         };
         bot.send_message(msg.chat.id, message).await?;
         enter_default_state(bot, msg.chat.id, dialogue).await?;
@@ -166,10 +160,18 @@ async fn send_confirm_delete_category(bot: Bot, chat_id: ChatId, dialogue: MyDia
         .resize_keyboard()
         .one_time_keyboard();
 
-    bot.send_message(chat_id, format!("Вы уверены, что хотите удалить категорию '{}'? Все траты из этой категории перейдут в категорию 'Другое'", category))
+    bot.send_message(chat_id, format!(
+        "Вы уверены, что хотите удалить категорию '{}' вместе с её подкатегориями? Все траты из них перейдут в категорию '{}'",
+        category,
+        deleted_category_target(&category)))
         .reply_markup(keyboard)
         .await?;
 
     dialogue.update(State::ConfirmDeleteCategory { category }).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+// Траты удалённой подкатегории переходят к родителю, а категории верхнего уровня - в категорию по умолчанию
+fn deleted_category_target(category: &str) -> &str {
+    parent_category(category).unwrap_or(DEFAULT_OTHER_CATEGORY)
+}
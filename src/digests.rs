@@ -0,0 +1,150 @@
+use crate::*;
+
+use chrono::{Datelike, Weekday};
+
+pub async fn show_summary(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let args = args.trim();
+    let period = if args.is_empty() { Some(Period::Month) } else { Period::parse(args) };
+    let Some(period) = period else {
+        bot.send_message(msg.chat.id, "Укажите период: сегодня, неделя, месяц, год или месяц в формате 10.2026").await?;
+        return Ok(());
+    };
+
+    bot.send_message(msg.chat.id, build_summary(user_entry, period, Utc::now())).await?;
+    Ok(())
+}
+
+pub async fn handle_digest_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /digest");
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let message = match words.as_slice() {
+        [] => match &user_entry.digest {
+            Some(digest) => format!("Сводка приходит {}. Отключить: /digest выкл", describe_digest(digest)),
+            None => String::from("Сводка отключена. Включить: /digest ежедневно 21:00"),
+        },
+        [off] if ["выкл", "off"].contains(&off.to_lowercase().as_str()) => {
+            user_entry.digest = None;
+            String::from("Сводка отключена")
+        }
+        [frequency, time] => {
+            let frequency = parse_digest_frequency(frequency);
+            let time = NaiveTime::parse_from_str(time, "%H:%M").ok();
+            let Some((frequency, time)) = frequency.zip(time) else {
+                bot.send_message(msg.chat.id, "Не понимаю настройку. Пример: /digest еженедельно 20:00").await?;
+                return Ok(());
+            };
+
+            let digest = DigestSettings { frequency, time, last_sent: None };
+            let message = format!("Сводка будет приходить {}", describe_digest(&digest));
+            user_entry.digest = Some(digest);
+            message
+        }
+        _ => {
+            bot.send_message(msg.chat.id, "Не понимаю настройку. Пример: /digest ежедневно 21:00 или /digest выкл").await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+// Отправляет сводки пользователям, у которых наступило настроенное время
+pub async fn process_digests(
+    bot: &Bot,
+    user_data: &Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let now = Utc::now();
+    let mut digests: Vec<(ChatId, String)> = Vec::new();
+
+    let mut data = user_data.lock().await;
+    for (chat_id, user_entry) in data.iter_mut() {
+        let local_now = now.with_timezone(&user_entry.timezone());
+        let today = local_now.date_naive();
+        let Some(digest) = &user_entry.digest else {
+            continue;
+        };
+
+        if digest.last_sent == Some(today) || local_now.time() < digest.time || !is_digest_day(digest.frequency, today) {
+            continue;
+        }
+
+        let period = digest_period(digest.frequency);
+        digests.push((*chat_id, build_summary(user_entry, period, now)));
+        if let Some(digest) = &mut user_entry.digest {
+            digest.last_sent = Some(today);
+        }
+    }
+
+    if digests.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+    drop(data);
+
+    for (chat_id, text) in digests {
+        if let Err(e) = bot.send_message(chat_id, text).await {
+            warn!("Failed to send digest to {}: {}", chat_id, e);
+            record_telegram_error();
+        }
+    }
+    Ok(())
+}
+
+// Сводка подводит итоги текущего периода, поэтому недельная приходит в воскресенье, а месячная - в последний день месяца
+fn is_digest_day(frequency: DigestFrequency, today: NaiveDate) -> bool {
+    match frequency {
+        DigestFrequency::Daily => true,
+        DigestFrequency::Weekly => today.weekday() == Weekday::Sun,
+        DigestFrequency::Monthly => today.succ_opt().is_some_and(|tomorrow| tomorrow.month() != today.month()),
+    }
+}
+
+fn digest_period(frequency: DigestFrequency) -> Period {
+    match frequency {
+        DigestFrequency::Daily => Period::Day,
+        DigestFrequency::Weekly => Period::Week,
+        DigestFrequency::Monthly => Period::Month,
+    }
+}
+
+fn parse_digest_frequency(word: &str) -> Option<DigestFrequency> {
+    match word.to_lowercase().as_str() {
+        "ежедневно" | "daily" => Some(DigestFrequency::Daily),
+        "еженедельно" | "weekly" => Some(DigestFrequency::Weekly),
+        "ежемесячно" | "monthly" => Some(DigestFrequency::Monthly),
+        _ => None,
+    }
+}
+
+fn describe_digest(digest: &DigestSettings) -> String {
+    let frequency = match digest.frequency {
+        DigestFrequency::Daily => "каждый день",
+        DigestFrequency::Weekly => "по воскресеньям",
+        DigestFrequency::Monthly => "в последний день месяца",
+    };
+    format!("{} в {}", frequency, digest.time.format("%H:%M"))
+}
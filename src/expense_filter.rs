@@ -0,0 +1,105 @@
+use crate::*;
+
+pub const TAG_PREFIX: char = '#';
+
+#[derive(Debug, Clone, Default)]
+pub struct ExpenseFilter {
+    pub tags: Vec<String>,
+    pub period: Option<Period>,
+    pub account: Option<String>,
+    range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl ExpenseFilter {
+    // Разбирает аргументы команды отчёта, например: "#отпуск #сочи @карта месяц"
+    pub fn parse(args: &str, timezone: FixedOffset) -> Self {
+        let mut filter = ExpenseFilter::default();
+        for word in args.split_whitespace() {
+            if let Some(tag) = parse_tag(word) {
+                if !filter.tags.contains(&tag) {
+                    filter.tags.push(tag);
+                }
+            } else if let Some(account) = parse_account_mention(word) {
+                filter.account = Some(account);
+            } else if let Some(period) = Period::parse(word) {
+                filter.period = Some(period);
+                filter.range = Some(period.range(timezone, Utc::now()));
+            }
+        }
+        filter
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.period.is_none() && self.account.is_none()
+    }
+
+    pub fn matches(&self, expense: &Expense) -> bool {
+        self.tags.iter().all(|tag| expense.tags.contains(tag))
+            && self.matches_account(&expense.account)
+            && self.matches_date(expense.date)
+    }
+
+    // Теги есть только у трат, поэтому доходы фильтруются по счёту и периоду
+    pub fn matches_income(&self, income: &Income) -> bool {
+        self.matches_account(&income.account) && self.matches_date(income.date)
+    }
+
+    fn matches_account(&self, account: &Option<String>) -> bool {
+        match (&self.account, account) {
+            (None, _) => true,
+            (Some(filter), Some(account)) => filter.to_lowercase() == account.to_lowercase(),
+            (Some(_), None) => false,
+        }
+    }
+
+    pub fn matches_date(&self, date: DateTime<Utc>) -> bool {
+        self.range.is_none_or(|(start, end)| start <= date && date < end)
+    }
+
+    // Строка, из которой parse восстановит этот же фильтр
+    pub fn to_args(&self) -> String {
+        let mut args = format_tags(&self.tags);
+        if let Some(account) = &self.account {
+            if !args.is_empty() {
+                args.push(' ');
+            }
+            args.push_str(&format!("{}{}", ACCOUNT_PREFIX, account));
+        }
+        if let Some(period) = self.period {
+            if !args.is_empty() {
+                args.push(' ');
+            }
+            args.push_str(&period.to_arg());
+        }
+        args
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        if let Some(period) = self.period {
+            description.push_str(&format!(" {}", period.label()));
+        }
+        if let Some(account) = &self.account {
+            description.push_str(&format!(" по счёту '{}'", account));
+        }
+        if !self.tags.is_empty() {
+            description.push_str(&format!(" (фильтр: {})", format_tags(&self.tags)));
+        }
+        description
+    }
+}
+
+pub fn parse_tag(word: &str) -> Option<String> {
+    let tag = word.strip_prefix(TAG_PREFIX)?;
+    if tag.is_empty() || !tag.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return None;
+    }
+    Some(tag.to_lowercase())
+}
+
+pub fn format_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("{}{}", TAG_PREFIX, tag))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
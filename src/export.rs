@@ -0,0 +1,218 @@
+use crate::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::Datelike;
+use rust_xlsxwriter::{Format, Workbook};
+
+pub const EXPORT_CALLBACK_PREFIX: &str = "export:";
+const EXPORT_FILTER_SEPARATOR: char = '|';
+// BOM нужен, чтобы Excel открыл файл в UTF-8 и не испортил кириллицу
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+// Excel с русской локалью разделяет столбцы точкой с запятой
+const CSV_DELIMITER: u8 = b';';
+const EXPENSE_COLUMNS: [&str; 8] = ["Дата", "Описание", "Сумма", "Категория", "Счёт", "Теги", "Заметка", "Вложение"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Xlsx,
+    Json,
+    Ofx,
+    Qif,
+}
+
+impl ExportFormat {
+    const ALL: [ExportFormat; 5] = [ExportFormat::Csv, ExportFormat::Xlsx, ExportFormat::Json, ExportFormat::Ofx, ExportFormat::Qif];
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Json => "json",
+            ExportFormat::Ofx => "ofx",
+            ExportFormat::Qif => "qif",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Xlsx => "Excel",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Ofx => "OFX",
+            ExportFormat::Qif => "QIF",
+        }
+    }
+
+    fn parse(extension: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.extension() == extension)
+    }
+}
+
+pub async fn handle_export_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /export");
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    if !user_entry.expenses.iter().any(|e| filter.matches(e)) {
+        bot.send_message(msg.chat.id, format!("Нет трат{}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    // Фильтр с длинными тегами не помещается в кнопку, тогда сразу отдаём CSV
+    let callback_data: Vec<String> = ExportFormat::ALL.iter().map(|format| export_callback_data(*format, &filter)).collect();
+    if callback_data.iter().any(|data| data.len() > MAX_CALLBACK_DATA_LEN) {
+        let file = build_export_file(user_entry, &filter, ExportFormat::Csv)?;
+        drop(data);
+        bot.send_document(msg.chat.id, file).caption(format!("Траты{}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    let buttons: Vec<InlineKeyboardButton> = ExportFormat::ALL.iter().zip(callback_data)
+        .map(|(format, data)| InlineKeyboardButton::callback(format.label(), data))
+        .collect();
+    bot.send_message(msg.chat.id, format!("В каком формате выгрузить траты{}?", filter.describe()))
+        .reply_markup(InlineKeyboardMarkup::new(buttons.chunks(3).map(|row| row.to_vec())))
+        .await?;
+    Ok(())
+}
+
+pub async fn handle_export_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    // Формат: export:<расширение>|<аргументы фильтра>
+    let data = q.data.as_deref().unwrap_or_default()[EXPORT_CALLBACK_PREFIX.len()..].to_string();
+    let (extension, filter_args) = data.split_once(EXPORT_FILTER_SEPARATOR).unwrap_or((&data, ""));
+    let Some(format) = ExportFormat::parse(extension) else {
+        return Ok(());
+    };
+    info!("Export callback: {}, filter: '{}'", extension, filter_args);
+
+    let Some(message) = q.regular_message() else {
+        info!("Callback message is inaccessible");
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+    let filter = ExpenseFilter::parse(filter_args, user_entry.timezone());
+    let file = build_export_file(user_entry, &filter, format)?;
+    drop(data);
+
+    bot.edit_message_text(message.chat.id, message.id, format!("Выгрузка трат{} в {}", filter.describe(), format.label())).await?;
+    bot.send_document(message.chat.id, file).await?;
+    Ok(())
+}
+
+fn build_export_file(user_entry: &UserData, filter: &ExpenseFilter, format: ExportFormat) -> Result<InputFile, Box<dyn Error + Send + Sync>> {
+    let content = match format {
+        ExportFormat::Csv => build_expenses_csv(user_entry, filter)?,
+        ExportFormat::Xlsx => build_expenses_xlsx(user_entry, filter)?,
+        ExportFormat::Json => build_expenses_json(user_entry, filter)?,
+        ExportFormat::Ofx => build_ofx(&filtered_expenses(user_entry, filter)).into_bytes(),
+        ExportFormat::Qif => build_qif(&filtered_expenses(user_entry, filter), user_entry.timezone()).into_bytes(),
+    };
+    Ok(InputFile::memory(content).file_name(export_file_name(filter, format.extension())))
+}
+
+fn build_expenses_csv(user_entry: &UserData, filter: &ExpenseFilter) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let mut writer = csv::WriterBuilder::new().delimiter(CSV_DELIMITER).from_writer(UTF8_BOM.to_vec());
+    writer.write_record(EXPENSE_COLUMNS)?;
+    for expense in user_entry.expenses.iter().filter(|e| filter.matches(e)) {
+        writer.write_record(expense_row(expense, user_entry.timezone()))?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+// Первый лист - все траты, второй - суммы по категориям в разбивке по месяцам
+fn build_expenses_xlsx(user_entry: &UserData, filter: &ExpenseFilter) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let timezone = user_entry.timezone();
+    let expenses = filtered_expenses(user_entry, filter);
+    let bold = Format::new().set_bold();
+    let money = Format::new().set_num_format("0.00");
+    let mut workbook = Workbook::new();
+
+    let sheet = workbook.add_worksheet().set_name("Траты")?;
+    for (col, title) in EXPENSE_COLUMNS.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &bold)?;
+    }
+    for (row, expense) in expenses.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, value) in expense_row(expense, timezone).iter().enumerate() {
+            sheet.write_string(row, col as u16, value)?;
+        }
+        sheet.write_number_with_format(row, 2, expense.amount, &money)?;
+    }
+
+    let mut totals: BTreeMap<String, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+    let mut months = BTreeSet::new();
+    for expense in &expenses {
+        let month = expense.date.with_timezone(&timezone).date_naive().with_day(1).unwrap();
+        months.insert(month);
+        *totals.entry(expense.category.clone()).or_default().entry(month).or_insert(0.0) += expense.amount;
+    }
+
+    let sheet = workbook.add_worksheet().set_name("По месяцам")?;
+    sheet.write_string_with_format(0, 0, "Категория", &bold)?;
+    for (col, month) in months.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16 + 1, month.format("%m.%Y").to_string(), &bold)?;
+    }
+    for (row, (category, category_totals)) in totals.iter().enumerate() {
+        let row = row as u32 + 1;
+        sheet.write_string(row, 0, category)?;
+        for (col, month) in months.iter().enumerate() {
+            if let Some(total) = category_totals.get(month) {
+                sheet.write_number_with_format(row, col as u16 + 1, *total, &money)?;
+            }
+        }
+    }
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+// Те же записи, что и в users_data.json, поэтому файл можно загрузить обратно в бота
+fn build_expenses_json(user_entry: &UserData, filter: &ExpenseFilter) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    Ok(serde_json::to_vec_pretty(&filtered_expenses(user_entry, filter))?)
+}
+
+fn filtered_expenses<'a>(user_entry: &'a UserData, filter: &ExpenseFilter) -> Vec<&'a Expense> {
+    user_entry.expenses.iter().filter(|e| filter.matches(e)).collect()
+}
+
+fn expense_row(expense: &Expense, timezone: FixedOffset) -> [String; 8] {
+    [
+        expense.date.with_timezone(&timezone).format("%d.%m.%Y %H:%M").to_string(),
+        expense.description.clone(),
+        // Excel с русской локалью понимает числа только с запятой; в xlsx сумма всё равно пишется числом
+        format!("{:.2}", expense.amount).replace('.', ","),
+        expense.category.clone(),
+        expense.account.clone().unwrap_or_default(),
+        format_tags(&expense.tags),
+        expense.note.clone().unwrap_or_default(),
+        String::from(if expense.attachment.is_some() { "да" } else { "" }),
+    ]
+}
+
+fn export_callback_data(format: ExportFormat, filter: &ExpenseFilter) -> String {
+    format!("{}{}{}{}", EXPORT_CALLBACK_PREFIX, format.extension(), EXPORT_FILTER_SEPARATOR, filter.to_args())
+}
+
+// Например: expenses_09.2026.csv или expenses.csv, если период не указан
+fn export_file_name(filter: &ExpenseFilter, extension: &str) -> String {
+    match filter.period {
+        Some(period) => format!("expenses_{}.{}", period.to_arg(), extension),
+        None => format!("expenses.{}", extension),
+    }
+}
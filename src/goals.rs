@@ -0,0 +1,288 @@
+use crate::*;
+
+pub const GOAL_CALLBACK_PREFIX: &str = "goal:";
+// Доли дохода, которые предлагается отложить на цель после добавления дохода
+const INCOME_CONTRIBUTION_PERCENTS: [u32; 3] = [10, 25, 50];
+const AVERAGE_DAYS_IN_MONTH: f64 = 30.44;
+
+pub async fn handle_add_goal_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /addgoal");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let goal = match words.as_slice() {
+        [name @ .., target, deadline] if !name.is_empty() => {
+            let target = target.parse::<f64>().ok().filter(|target| target.is_finite() && *target > 0.0);
+            let deadline = NaiveDate::parse_from_str(deadline, "%d.%m.%Y").ok();
+            target.zip(deadline).map(|(target, deadline)| Goal {
+                name: name.join(" "),
+                target,
+                deadline,
+                contributions: Vec::new(),
+            })
+        }
+        _ => None,
+    };
+
+    let Some(goal) = goal else {
+        bot.send_message(msg.chat.id, "Укажите цель в формате: /addgoal название сумма дд.мм.гггг, например: /addgoal Отпуск 200000 31.12.2026").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if find_goal(user_entry, &goal.name).is_some() {
+        bot.send_message(msg.chat.id, "Такая цель уже добавлена").await?;
+        return Ok(());
+    }
+
+    let message = format!(
+        "Цель '{}' на сумму {:.2} до {} добавлена. Откладывайте на неё командой /contribute {} сумма",
+        goal.name,
+        goal.target,
+        goal.deadline.format("%d.%m.%Y"),
+        goal.name
+    );
+    user_entry.goals.push(goal);
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_contribute_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /contribute");
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let contribution = match words.as_slice() {
+        [name @ .., amount] if !name.is_empty() => amount.parse::<f64>().ok()
+            .filter(|amount| amount.is_finite() && *amount != 0.0)
+            .map(|amount| (name.join(" "), amount)),
+        _ => None,
+    };
+
+    let Some((name, amount)) = contribution else {
+        bot.send_message(msg.chat.id, "Укажите цель и сумму, например: /contribute Отпуск 5000. Чтобы забрать деньги с цели, укажите отрицательную сумму").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let Some(goal) = find_goal_mut(user_entry, &name) else {
+        bot.send_message(msg.chat.id, "Нет такой цели. Список целей: /goals").await?;
+        return Ok(());
+    };
+
+    goal.contributions.push(Contribution { amount, date: Utc::now(), source: ContributionSource::Manual, income_index: None });
+    let message = describe_contribution(goal, amount);
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_goals(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.goals.is_empty() {
+        bot.send_message(msg.chat.id, "У вас нет целей накоплений. Добавьте цель командой /addgoal, например: /addgoal Отпуск 200000 31.12.2026").await?;
+        return Ok(());
+    }
+
+    let today = Utc::now().with_timezone(&user_entry.timezone()).date_naive();
+    let monthly_savings = monthly_savings_rate(user_entry);
+
+    let mut message = String::from("Ваши цели:\n\n");
+    for goal in &user_entry.goals {
+        message.push_str(&describe_goal_progress(goal, today, monthly_savings));
+        message.push_str("\n\n");
+    }
+
+    match monthly_savings {
+        Some(rate) => message.push_str(&format!("Ваш средний темп сбережений: {:.2} в месяц (доходы минус расходы)", rate)),
+        None => message.push_str("Добавляйте доходы (+зарплата 150000), чтобы бот мог посчитать ваш темп сбережений"),
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_goal_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    // Формат: goal:<номер цели>:<процент>:<номер дохода>
+    let data = q.data.as_deref().unwrap_or_default()[GOAL_CALLBACK_PREFIX.len()..].to_string();
+    let parts: Option<Vec<usize>> = data.split(':').map(|part| part.parse::<usize>().ok()).collect();
+    let Some([goal_index, percent, income_index]) = parts.as_deref() else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    info!("Goal contribution callback: goal {}, {}% of income {}", goal_index, percent, income_index);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+
+    let Some(income_amount) = user_entry.incomes.get(*income_index).map(|income| income.amount) else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    let Some(goal) = user_entry.goals.get_mut(*goal_index) else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+
+    // Кнопки остаются нажимаемыми, пока сообщение не отредактировано: повторное нажатие ничего не добавляет
+    if goal.contributions.iter().any(|contribution| contribution.income_index == Some(*income_index)) {
+        bot.answer_callback_query(q.id.clone()).text("Часть этого дохода уже отложена на цель").await?;
+        return Ok(());
+    }
+
+    let amount = income_amount * *percent as f64 / 100.0;
+    goal.contributions.push(Contribution {
+        amount,
+        date: Utc::now(),
+        source: ContributionSource::Income,
+        income_index: Some(*income_index),
+    });
+    let text = describe_contribution(goal, amount);
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+    drop(data);
+
+    bot.answer_callback_query(q.id.clone()).await?;
+    if let Some(message) = q.regular_message() {
+        bot.edit_message_text(message.chat.id, message.id, text).await?;
+    }
+    Ok(())
+}
+
+// Кнопки, которыми можно отложить часть только что добавленного дохода на одну из целей
+pub fn goal_contribution_keyboard(user_entry: &UserData, income_index: usize) -> Option<InlineKeyboardMarkup> {
+    if user_entry.goals.is_empty() {
+        return None;
+    }
+
+    let buttons = user_entry.goals.iter().enumerate()
+        .map(|(goal_index, goal)| {
+            INCOME_CONTRIBUTION_PERCENTS.iter()
+                .map(|percent| InlineKeyboardButton::callback(
+                    format!("{}% → {}", percent, goal.name),
+                    format!("{}{}:{}:{}", GOAL_CALLBACK_PREFIX, goal_index, percent, income_index)
+                ))
+                .collect()
+        })
+        .collect::<Vec<Vec<InlineKeyboardButton>>>();
+
+    Some(InlineKeyboardMarkup::new(buttons))
+}
+
+pub fn goal_saved(goal: &Goal) -> f64 {
+    goal.contributions.iter().map(|contribution| contribution.amount).sum()
+}
+
+// Средние сбережения в месяц за всё время учёта: доходы минус расходы
+pub fn monthly_savings_rate(user_entry: &UserData) -> Option<f64> {
+    let first_date = user_entry.incomes.iter().map(|income| income.date).min()?;
+    let first_date = user_entry.expenses.iter().map(|expense| expense.date).min()
+        .map_or(first_date, |date| date.min(first_date));
+
+    let total_income: f64 = user_entry.incomes.iter().map(|income| income.amount).sum();
+    let total_expenses: f64 = user_entry.expenses.iter().map(|expense| expense.amount).sum();
+    let months = ((Utc::now() - first_date).num_days() as f64 / AVERAGE_DAYS_IN_MONTH).max(1.0);
+
+    Some((total_income - total_expenses) / months)
+}
+
+fn describe_goal_progress(goal: &Goal, today: NaiveDate, monthly_savings: Option<f64>) -> String {
+    let saved = goal_saved(goal);
+    let remaining = goal.target - saved;
+    let mut description = format!(
+        "**{}**: {:.2} из {:.2} ({:.0}%), срок: {}",
+        goal.name,
+        saved,
+        goal.target,
+        saved / goal.target * 100.0,
+        goal.deadline.format("%d.%m.%Y")
+    );
+
+    if remaining <= 0.0 {
+        description.push_str("\nЦель достигнута 🎉");
+        return description;
+    }
+
+    let months_left = (goal.deadline - today).num_days() as f64 / AVERAGE_DAYS_IN_MONTH;
+    if months_left <= 0.0 {
+        description.push_str(&format!("\nСрок прошёл, не хватает {:.2}", remaining));
+        return description;
+    }
+
+    description.push_str(&format!("\nНужно откладывать {:.2} в месяц", remaining / months_left.max(1.0)));
+
+    match monthly_savings {
+        Some(rate) if rate > 0.0 => {
+            let projected = saved + rate * months_left;
+            if projected >= goal.target {
+                let months_needed = remaining / rate;
+                let reach_date = today + chrono::Duration::days((months_needed * AVERAGE_DAYS_IN_MONTH).ceil() as i64);
+                description.push_str(&format!("\nПри текущем темпе цель будет достигнута примерно {}", reach_date.format("%d.%m.%Y")));
+            } else {
+                description.push_str(&format!(
+                    "\nПри текущем темпе к сроку накопится около {:.2}, не хватит {:.2}",
+                    projected,
+                    goal.target - projected
+                ));
+            }
+        }
+        Some(_) => description.push_str("\nСейчас расходы не меньше доходов, при таком темпе цель не будет достигнута"),
+        None => {}
+    }
+    description
+}
+
+fn describe_contribution(goal: &Goal, amount: f64) -> String {
+    let saved = goal_saved(goal);
+    let action = if amount >= 0.0 { "Отложено" } else { "Снято" };
+    format!(
+        "{} {:.2} на цель '{}'. Накоплено {:.2} из {:.2} ({:.0}%)",
+        action,
+        amount.abs(),
+        goal.name,
+        saved,
+        goal.target,
+        saved / goal.target * 100.0
+    )
+}
+
+fn find_goal<'a>(user_entry: &'a UserData, name: &str) -> Option<&'a Goal> {
+    let name = name.to_lowercase();
+    user_entry.goals.iter().find(|goal| goal.name.to_lowercase() == name)
+}
+
+fn find_goal_mut<'a>(user_entry: &'a mut UserData, name: &str) -> Option<&'a mut Goal> {
+    let name = name.to_lowercase();
+    user_entry.goals.iter_mut().find(|goal| goal.name.to_lowercase() == name)
+}
@@ -0,0 +1,764 @@
+use crate::*;
+
+use calamine::{Data, DataType, Reader, Xlsx};
+use chrono::Duration;
+
+const JSON_EXTENSION: &str = ".json";
+const TABLE_EXTENSIONS: [&str; 2] = [".csv", ".xlsx"];
+const XLSX_EXTENSION: &str = ".xlsx";
+const OFX_EXTENSION: &str = ".ofx";
+const QIF_EXTENSION: &str = ".qif";
+// Строки над заголовками, которые банки добавляют в начало выписки
+const MAX_HEADER_ROW: usize = 20;
+pub const IMPORT_PREVIEW_ROWS: usize = 5;
+const MAX_REPORTED_BAD_ROWS: usize = 10;
+const SKIP_COLUMN_BUTTON: &str = "Пропустить";
+const ADD_ACCOUNTS_BUTTON: &str = "Добавить счета";
+const WITHOUT_ACCOUNTS_BUTTON: &str = "Без счетов";
+// %Y принимает и две цифры ("19.10.26" стал бы 26-м годом), поэтому %y проверяем раньше: он требует ровно две
+const DATE_TIME_FORMATS: [&str; 6] = [
+    "%d.%m.%y %H:%M", "%d.%m.%y %H:%M:%S", "%d.%m.%Y %H:%M", "%d.%m.%Y %H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"
+];
+const DATE_FORMATS: [&str; 5] = ["%d.%m.%y", "%d.%m.%Y", "%Y-%m-%d", "%d/%m/%y", "%d/%m/%Y"];
+
+// Как понять, что трата из файла уже записана
+#[derive(Debug, Clone, Copy)]
+pub enum DuplicateCheck {
+    // Дата из файла обрезана до секунды, минуты или дня: ищем трату с тем же описанием и суммой в пределах этой точности
+    SameTime(Duration),
+    // То же, но название в файле обрезано до этой длины, как в выгрузке OFX
+    SameTimeShortName(Duration, usize),
+    // Трату, записанную вручную, могли внести на день-два раньше или позже списания по банку
+    NearDate(Duration),
+}
+
+// Операция из файла OFX или QIF. Сумма со знаком: расходы отрицательные
+#[derive(Debug, Clone)]
+pub struct ImportedTransaction {
+    pub date: DateTime<Utc>,
+    pub check: DuplicateCheck,
+    pub amount: f64,
+    pub description: String,
+    pub category: Option<String>,
+    pub memo: Option<String>,
+    pub account: Option<String>,
+    pub is_transfer: bool,
+}
+
+impl ImportField {
+    fn name(&self) -> &'static str {
+        match self {
+            ImportField::Date => "дата",
+            ImportField::Amount => "сумма",
+            ImportField::Description => "описание",
+            ImportField::Category => "категория",
+        }
+    }
+
+    fn next(&self) -> Option<ImportField> {
+        match self {
+            ImportField::Date => Some(ImportField::Amount),
+            ImportField::Amount => Some(ImportField::Description),
+            ImportField::Description => Some(ImportField::Category),
+            ImportField::Category => None,
+        }
+    }
+
+    fn previous(&self) -> Option<ImportField> {
+        match self {
+            ImportField::Date => None,
+            ImportField::Amount => Some(ImportField::Date),
+            ImportField::Description => Some(ImportField::Amount),
+            ImportField::Category => Some(ImportField::Description),
+        }
+    }
+}
+
+impl PendingImport {
+    fn set_column(&mut self, field: ImportField, column: Option<usize>) {
+        match field {
+            ImportField::Date => self.date_column = column,
+            ImportField::Amount => self.amount_column = column,
+            ImportField::Description => self.description_column = column,
+            ImportField::Category => self.category_column = column,
+        }
+    }
+}
+
+// Документ .json без подписи считаем выгрузкой из /export, подпись означает трату с вложением
+pub fn is_json_import_document(msg: Message, me: Me) -> bool {
+    is_without_caption(&msg, &me) && is_document_with_extension(&msg, JSON_EXTENSION)
+}
+
+pub fn is_interchange_import_document(msg: Message, me: Me) -> bool {
+    is_without_caption(&msg, &me)
+        && (is_document_with_extension(&msg, OFX_EXTENSION) || is_document_with_extension(&msg, QIF_EXTENSION))
+}
+
+pub fn is_table_import_document(msg: Message, me: Me) -> bool {
+    is_without_caption(&msg, &me) && TABLE_EXTENSIONS.iter().any(|extension| is_document_with_extension(&msg, extension))
+}
+
+pub async fn handle_json_import(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got JSON import document");
+    let document = msg.document().unwrap();
+    let content = download_document(&bot, &document.file.id).await?;
+
+    let expenses: Vec<Expense> = match serde_json::from_slice(&content) {
+        Ok(expenses) => expenses,
+        Err(e) => {
+            info!("JSON import parse error: {}", e);
+            bot.send_message(msg.chat.id, "Не удалось прочитать файл. Загрузите JSON, полученный командой /export").await?;
+            return Ok(());
+        }
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let expenses = expenses.into_iter().map(|expense| (expense, DuplicateCheck::SameTime(Duration::seconds(1)))).collect();
+    let (imported, duplicates) = import_expenses(user_entry, expenses, msg.from.as_ref().map(|user| user.id));
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, describe_import_result(imported, duplicates)).await?;
+    Ok(())
+}
+
+pub async fn handle_interchange_import(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got OFX/QIF import document");
+    let document = msg.document().unwrap();
+    let content = download_document(&bot, &document.file.id).await?;
+    let text = decode_text(&content);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let timezone = user_entry.timezone();
+    let transactions = if is_document_with_extension(&msg, OFX_EXTENSION) {
+        parse_ofx(&text, timezone)
+    } else {
+        parse_qif(&text, timezone)
+    };
+
+    if transactions.is_empty() {
+        bot.send_message(msg.chat.id, "Не удалось найти операции в файле").await?;
+        return Ok(());
+    }
+
+    // Счета сами не заводим: спрашиваем, добавить их или загрузить траты без счёта
+    let new_accounts = unknown_accounts(user_entry, &transactions);
+    if !new_accounts.is_empty() {
+        let pending_interchange_import = PendingInterchangeImport { transactions, new_accounts };
+        send_confirm_import_accounts(bot, msg.chat.id, dialogue, pending_interchange_import).await?;
+        return Ok(());
+    }
+
+    let message = import_transactions(user_entry, transactions, false, msg.from.as_ref().map(|user| user.id));
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_confirm_import_accounts(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    pending_interchange_import: PendingInterchangeImport,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let create_accounts = match msg.text() {
+        Some(ADD_ACCOUNTS_BUTTON) => true,
+        Some(WITHOUT_ACCOUNTS_BUTTON) => false,
+        _ => {
+            enter_default_state(bot, msg.chat.id, dialogue).await?;
+            return Ok(());
+        }
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let message = import_transactions(
+        user_entry,
+        pending_interchange_import.transactions,
+        create_accounts,
+        msg.from.as_ref().map(|user| user.id)
+    );
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    enter_default_state(bot, msg.chat.id, dialogue).await?;
+    Ok(())
+}
+
+async fn send_confirm_import_accounts(
+    bot: Bot,
+    chat_id: ChatId,
+    dialogue: MyDialogue,
+    pending_interchange_import: PendingInterchangeImport
+) -> HandlerResult {
+    let keyboard = KeyboardMarkup::new(
+        vec![
+            vec![KeyboardButton::new(ADD_ACCOUNTS_BUTTON), KeyboardButton::new(WITHOUT_ACCOUNTS_BUTTON)],
+            vec![KeyboardButton::new("Отменить")],
+        ])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    let accounts = pending_interchange_import.new_accounts.iter()
+        .map(|account| format!("{}{}", ACCOUNT_PREFIX, account))
+        .collect::<Vec<String>>()
+        .join(", ");
+    bot.send_message(chat_id, format!("В файле есть счета, которых у вас нет: {}. Добавить их или загрузить траты без счёта?", accounts))
+        .reply_markup(keyboard)
+        .await?;
+
+    info!("Changing state to ConfirmImportAccounts");
+    dialogue.update(State::ConfirmImportAccounts { pending_interchange_import }).await?;
+    Ok(())
+}
+
+// Загружает траты из операций OFX или QIF и возвращает отчёт для пользователя
+fn import_transactions(
+    user_entry: &mut UserData,
+    transactions: Vec<ImportedTransaction>,
+    create_accounts: bool,
+    author: Option<UserId>
+) -> String {
+    let total = transactions.len();
+    let (expenses, created_accounts) = convert_imported_transactions(user_entry, transactions, create_accounts);
+    let skipped = total - expenses.len();
+    let (imported, duplicates) = import_expenses(user_entry, expenses, author);
+
+    let mut message = describe_import_result(imported, duplicates);
+    if skipped > 0 {
+        message.push_str(&format!("\nПропущено поступлений и переводов: {}", skipped));
+    }
+    if !created_accounts.is_empty() {
+        message.push_str(&format!("\nДобавлены счета: {}", created_accounts.join(", ")));
+    }
+    message
+}
+
+pub async fn handle_table_import(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got table import document");
+    let document = msg.document().unwrap();
+    let content = download_document(&bot, &document.file.id).await?;
+    let is_xlsx = is_document_with_extension(&msg, XLSX_EXTENSION);
+
+    let Some(pending_import) = read_table(&content, is_xlsx) else {
+        bot.send_message(msg.chat.id, "Не удалось прочитать таблицу. Нужен файл CSV или Excel со строкой заголовков").await?;
+        return Ok(());
+    };
+
+    // Выписки известных банков разбираем сами, для остальных таблиц спрашиваем столбцы
+    if let Some(layout) = detect_bank_layout(&pending_import.headers) {
+        let mut data = user_data.lock().await;
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
+        let pending_bank_import = convert_bank_statement(user_entry, layout, &pending_import);
+        send_confirm_bank_import(bot, msg.chat.id, user_entry, dialogue, pending_bank_import).await?;
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, format!("В файле {} строк. Укажите, в каких столбцах какие данные", pending_import.rows.len())).await?;
+    send_select_import_column(bot, msg.chat.id, dialogue, pending_import, ImportField::Date).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_select_import_column(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    (mut pending_import, field): (PendingImport, ImportField),
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let text = if let Some(text) = msg.text() {
+        text.trim().to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    if text == "Отменить" {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    if text == "Назад" {
+        match field.previous() {
+            Some(previous) => send_select_import_column(bot, msg.chat.id, dialogue, pending_import, previous).await?,
+            None => enter_default_state(bot, msg.chat.id, dialogue).await?,
+        }
+        return Ok(());
+    }
+
+    let column = if field == ImportField::Category && text == SKIP_COLUMN_BUTTON {
+        None
+    } else {
+        let column = pending_import.headers.iter().position(|header| *header == text)
+            .or_else(|| text.parse::<usize>().ok().filter(|column| *column < pending_import.headers.len()));
+        let Some(column) = column else {
+            bot.send_message(msg.chat.id, "Пожалуйста, выберите столбец из списка").await?;
+            send_select_import_column(bot, msg.chat.id, dialogue, pending_import, field).await?;
+            return Ok(());
+        };
+        Some(column)
+    };
+    pending_import.set_column(field, column);
+
+    if let Some(next) = field.next() {
+        send_select_import_column(bot, msg.chat.id, dialogue, pending_import, next).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    send_confirm_import(bot, msg.chat.id, user_entry, dialogue, pending_import).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_confirm_import(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    pending_import: PendingImport,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    match text.as_str() {
+        "Да" => {}
+        "Назад" => {
+            send_select_import_column(bot, msg.chat.id, dialogue, pending_import, ImportField::Category).await?;
+            return Ok(());
+        }
+        _ => {
+            enter_default_state(bot, msg.chat.id, dialogue).await?;
+            return Ok(());
+        }
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    let (expenses, _, _) = convert_import_rows(&pending_import, user_entry.timezone());
+    let (imported, duplicates) = import_expenses(user_entry, expenses, msg.from.as_ref().map(|user| user.id));
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, describe_import_result(imported, duplicates)).await?;
+    enter_default_state(bot, msg.chat.id, dialogue).await?;
+    Ok(())
+}
+
+async fn send_select_import_column(
+    bot: Bot,
+    chat_id: ChatId,
+    dialogue: MyDialogue,
+    pending_import: PendingImport,
+    field: ImportField
+) -> HandlerResult {
+    let mut buttons: Vec<Vec<KeyboardButton>> = pending_import.headers.chunks(2)
+        .map(|headers| headers.iter().map(KeyboardButton::new).collect())
+        .collect();
+    if field == ImportField::Category {
+        buttons.push(vec![KeyboardButton::new(SKIP_COLUMN_BUTTON)]);
+    }
+    buttons.push(vec![KeyboardButton::new("Отменить"), KeyboardButton::new("Назад")]);
+    let keyboard = KeyboardMarkup::new(buttons).resize_keyboard().one_time_keyboard();
+
+    let mut message = format!("В каком столбце {}?", field.name());
+    if let Some(row) = pending_import.rows.first() {
+        message.push_str("\n\nПервая строка файла:\n");
+        for (header, value) in pending_import.headers.iter().zip(row) {
+            message.push_str(&format!("{}: {}\n", header, value));
+        }
+    }
+    if field == ImportField::Category {
+        message.push_str(&format!("\nЕсли категории в файле нет, нажмите '{}', траты попадут в '{}'", SKIP_COLUMN_BUTTON, DEFAULT_OTHER_CATEGORY));
+    }
+
+    bot.send_message(chat_id, message).reply_markup(keyboard).await?;
+
+    info!("Changing state to SelectImportColumn");
+    dialogue.update(State::SelectImportColumn { pending_import, field }).await?;
+    Ok(())
+}
+
+async fn send_confirm_import(
+    bot: Bot,
+    chat_id: ChatId,
+    user_entry: &UserData,
+    dialogue: MyDialogue,
+    pending_import: PendingImport
+) -> HandlerResult {
+    let timezone = user_entry.timezone();
+    let (expenses, bad_rows, incomes) = convert_import_rows(&pending_import, timezone);
+    let duplicates = find_duplicates(&user_entry.expenses, expenses.iter().map(|(expense, check)| (expense, *check)))
+        .into_iter()
+        .filter(|duplicate| *duplicate)
+        .count();
+
+    let mut message = format!(
+        "Строк в файле: {}\nБудет импортировано: {}\nУже записаны: {}\n",
+        pending_import.rows.len(),
+        expenses.len() - duplicates,
+        duplicates
+    );
+    if !bad_rows.is_empty() {
+        let rows = bad_rows.iter().take(MAX_REPORTED_BAD_ROWS).map(|row| row.to_string()).collect::<Vec<String>>().join(", ");
+        message.push_str(&format!("Не удалось разобрать: {} (строки {})\n", bad_rows.len(), rows));
+    }
+    if incomes > 0 {
+        message.push_str(&format!("Пропущено поступлений и возвратов: {}\n", incomes));
+    }
+
+    message.push_str("\nПервые траты:\n");
+    for (expense, _) in expenses.iter().take(IMPORT_PREVIEW_ROWS) {
+        message.push_str(&format!(
+            "[{}] {}: {}, {:.2}\n",
+            expense.date.with_timezone(&timezone).format("%d.%m.%y"),
+            expense.category,
+            expense.description,
+            expense.amount
+        ));
+    }
+    message.push_str("\nИмпортировать?");
+
+    let keyboard = KeyboardMarkup::new(
+        vec![
+            vec![KeyboardButton::new("Да"), KeyboardButton::new("Нет")],
+            vec![KeyboardButton::new("Назад")],
+        ])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    bot.send_message(chat_id, message).reply_markup(keyboard).await?;
+
+    info!("Changing state to ConfirmImport");
+    dialogue.update(State::ConfirmImport { pending_import }).await?;
+    Ok(())
+}
+
+// Добавляет траты, пропуская уже записанные. Возвращает число добавленных и пропущенных
+pub fn import_expenses(user_entry: &mut UserData, expenses: Vec<(Expense, DuplicateCheck)>, author: Option<UserId>) -> (usize, usize) {
+    // Одинаковые строки внутри файла - это разные траты, сравниваем только с тем, что было до импорта
+    let is_duplicate = find_duplicates(&user_entry.expenses, expenses.iter().map(|(expense, check)| (expense, *check)));
+    let mut imported = 0;
+    let mut duplicates = 0;
+    for ((mut expense, _), is_duplicate) in expenses.into_iter().zip(is_duplicate) {
+        if is_duplicate {
+            duplicates += 1;
+            continue;
+        }
+
+        // Сообщения из другого чата или старой переписки к трате больше не относятся
+        expense.message_ids.clear();
+        expense.author = expense.author.or(author);
+        add_category_with_parents(user_entry, &expense.category);
+        user_entry.expenses.push(expense);
+        imported += 1;
+    }
+    record_expenses_added(ExpenseSource::Import, imported);
+    (imported, duplicates)
+}
+
+// Для каждой новой траты - записана ли она уже. Каждая существующая трата может совпасть только с одной новой,
+// иначе одна записанная вручную трата поглотила бы несколько одинаковых покупок из выписки
+pub fn find_duplicates<'a>(existing: &[Expense], expenses: impl IntoIterator<Item = (&'a Expense, DuplicateCheck)>) -> Vec<bool> {
+    let mut matched = vec![false; existing.len()];
+    expenses.into_iter()
+        .map(|(expense, check)| {
+            let found = existing.iter().enumerate()
+                .position(|(index, existing)| !matched[index] && is_same_expense(existing, expense, check));
+            if let Some(index) = found {
+                matched[index] = true;
+            }
+            found.is_some()
+        })
+        .collect()
+}
+
+// Выгрузки пишут суммы с точностью до копейки, поэтому и сравниваем их в копейках
+fn is_same_expense(existing: &Expense, expense: &Expense, check: DuplicateCheck) -> bool {
+    (existing.amount * 100.0).round() == (expense.amount * 100.0).round() && match check {
+        DuplicateCheck::SameTime(precision) => is_within(existing, expense, precision)
+            && existing.description.to_lowercase() == expense.description.to_lowercase(),
+        DuplicateCheck::SameTimeShortName(precision, name_len) => {
+            let short_name: String = existing.description.chars().take(name_len).collect();
+            is_within(existing, expense, precision) && short_name.trim().to_lowercase() == expense.description.to_lowercase()
+        }
+        DuplicateCheck::NearDate(window) => (existing.date - expense.date).abs() <= window,
+    }
+}
+
+fn is_within(existing: &Expense, expense: &Expense, precision: Duration) -> bool {
+    expense.date <= existing.date && existing.date < expense.date + precision
+}
+
+pub fn describe_import_result(imported: usize, duplicates: usize) -> String {
+    let mut message = format!("Импортировано трат: {}", imported);
+    if duplicates > 0 {
+        message.push_str(&format!("\nПропущено уже записанных: {}", duplicates));
+    }
+    message
+}
+
+// Поступления и переводы между счетами пропускаем. Счета из файла сопоставляем со счетами бота по названию,
+// недостающие добавляем, только если пользователь согласился. Возвращает траты и названия добавленных счетов
+fn convert_imported_transactions(
+    user_entry: &mut UserData,
+    transactions: Vec<ImportedTransaction>,
+    create_accounts: bool
+) -> (Vec<(Expense, DuplicateCheck)>, Vec<String>) {
+    let mut expenses = Vec::new();
+    let mut created_accounts = Vec::new();
+    for transaction in transactions {
+        if !is_imported_expense(&transaction) {
+            continue;
+        }
+
+        let account = match transaction_account(&transaction) {
+            Some(name) => match find_account(user_entry, &name) {
+                Some(account) => Some(account.name.clone()),
+                None if create_accounts => {
+                    user_entry.accounts.push(Account { name: name.clone(), initial_balance: 0.0 });
+                    created_accounts.push(name.clone());
+                    Some(name)
+                }
+                None => None,
+            },
+            None => None,
+        };
+
+        // Примечание, совпадающее с категорией пользователя, - это категория из нашей выгрузки OFX
+        let memo_category = transaction.memo.as_ref()
+            .and_then(|memo| user_entry.categories.iter().find(|category| category.to_lowercase() == memo.to_lowercase()))
+            .cloned();
+        let note = if memo_category.is_some() { None } else { transaction.memo };
+        let category = transaction.category
+            .or(memo_category)
+            .or_else(|| categorize_by_rules(user_entry, &transaction.description, None))
+            .unwrap_or_else(|| DEFAULT_OTHER_CATEGORY.to_string());
+
+        let expense = Expense {
+            description: transaction.description,
+            amount: -transaction.amount,
+            category,
+            date: transaction.date,
+            tags: Vec::new(),
+            note,
+            attachment: None,
+            message_ids: Vec::new(),
+            account,
+            author: None,
+            split: Vec::new(),
+        };
+        expenses.push((expense, transaction.check));
+    }
+    (expenses, created_accounts)
+}
+
+// Счета из файла, которых нет у пользователя, без повторов
+fn unknown_accounts(user_entry: &UserData, transactions: &[ImportedTransaction]) -> Vec<String> {
+    let mut accounts: Vec<String> = Vec::new();
+    for name in transactions.iter().filter(|transaction| is_imported_expense(transaction)).filter_map(transaction_account) {
+        if find_account(user_entry, &name).is_none() && !accounts.iter().any(|account| account.to_lowercase() == name.to_lowercase()) {
+            accounts.push(name);
+        }
+    }
+    accounts
+}
+
+fn is_imported_expense(transaction: &ImportedTransaction) -> bool {
+    transaction.amount < 0.0 && !transaction.is_transfer
+}
+
+// Название счёта одним словом, как его пишут в тратах после '@'
+fn transaction_account(transaction: &ImportedTransaction) -> Option<String> {
+    transaction.account.as_ref().map(|name| name.split_whitespace().collect::<Vec<&str>>().join("_"))
+}
+
+pub async fn download_document(bot: &Bot, file_id: &str) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let file = bot.get_file(file_id.to_string()).await?;
+    let mut content = Vec::new();
+    bot.download_file(&file.path, &mut content).await?;
+    Ok(content)
+}
+
+// Документ с подписью - это трата с вложением. Подпись из одного упоминания бота в группе не в счёт
+fn is_without_caption(msg: &Message, me: &Me) -> bool {
+    msg.caption().is_none_or(|caption| strip_bot_mention(caption, me).is_empty())
+}
+
+fn is_document_with_extension(msg: &Message, extension: &str) -> bool {
+    msg.document()
+        .and_then(|document| document.file_name.as_deref())
+        .is_some_and(|name| name.to_lowercase().ends_with(extension))
+}
+
+// Заголовками считаем первую строку, где заполнено хотя бы три ячейки: выше бывают название выписки и период
+fn read_table(content: &[u8], is_xlsx: bool) -> Option<PendingImport> {
+    let rows = if is_xlsx { read_xlsx_rows(content)? } else { read_csv_rows(content)? };
+    let header_row = rows.iter().take(MAX_HEADER_ROW)
+        .position(|row| row.iter().filter(|value| !value.is_empty()).count() >= 3)?;
+
+    let mut rows = rows.into_iter().skip(header_row);
+    let headers = rows.next()?;
+    let rows: Vec<Vec<String>> = rows.filter(|row| row.iter().any(|value| !value.is_empty())).collect();
+    if rows.is_empty() {
+        return None;
+    }
+    Some(PendingImport { headers, rows, ..Default::default() })
+}
+
+// Разделитель выбираем по самому частому символу: Excel в русской локали сохраняет CSV через ';'
+fn read_csv_rows(content: &[u8]) -> Option<Vec<Vec<String>>> {
+    let text = decode_text(content);
+    let text = text.trim_start_matches('\u{feff}');
+    let delimiter = [b';', b',', b'\t'].into_iter()
+        .max_by_key(|delimiter| text.lines().take(MAX_HEADER_ROW).map(|line| line.matches(*delimiter as char).count()).sum::<usize>())?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let rows = reader.records()
+        .filter_map(Result::ok)
+        .map(|record| record.iter().map(|value| value.trim().to_string()).collect())
+        .collect();
+    Some(rows)
+}
+
+fn read_xlsx_rows(content: &[u8]) -> Option<Vec<Vec<String>>> {
+    let mut workbook: Xlsx<_> = calamine::open_workbook_from_rs(std::io::Cursor::new(content)).ok()?;
+    let range = workbook.worksheet_range_at(0)?.ok()?;
+    let rows = range.rows()
+        .map(|row| row.iter().map(cell_to_string).collect())
+        .collect();
+    Some(rows)
+}
+
+fn cell_to_string(cell: &Data) -> String {
+    match cell.as_datetime() {
+        Some(date) if matches!(cell, Data::DateTime(_) | Data::DateTimeIso(_)) => date.format("%d.%m.%Y %H:%M:%S").to_string(),
+        _ => cell.to_string().trim().to_string(),
+    }
+}
+
+// Старые выписки (например, Т-Банка) бывают в windows-1251
+pub fn decode_text(content: &[u8]) -> String {
+    match std::str::from_utf8(content) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding_rs::WINDOWS_1251.decode(content).0.into_owned(),
+    }
+}
+
+// Траты с точностью их даты, номера строк файла, которые не удалось разобрать, и число пропущенных поступлений
+fn convert_import_rows(pending_import: &PendingImport, timezone: FixedOffset) -> (Vec<(Expense, DuplicateCheck)>, Vec<usize>, usize) {
+    // Если в файле есть суммы с минусом, расходы записаны со знаком, а положительные суммы - поступления и возвраты
+    let signed = pending_import.rows.iter().any(|row| row_amount(pending_import, row).is_some_and(|amount| amount < 0.0));
+
+    let mut expenses = Vec::new();
+    let mut bad_rows = Vec::new();
+    let mut incomes = 0;
+    for (index, row) in pending_import.rows.iter().enumerate() {
+        if signed && row_amount(pending_import, row).is_some_and(|amount| amount > 0.0) {
+            incomes += 1;
+            continue;
+        }
+
+        match convert_import_row(pending_import, row, timezone) {
+            Some(expense) => expenses.push(expense),
+            // Строка 1 - заголовки
+            None => bad_rows.push(index + 2),
+        }
+    }
+    (expenses, bad_rows, incomes)
+}
+
+fn row_amount(pending_import: &PendingImport, row: &[String]) -> Option<f64> {
+    pending_import.amount_column.and_then(|column| row.get(column)).and_then(|value| parse_signed_amount(value))
+}
+
+fn convert_import_row(pending_import: &PendingImport, row: &[String], timezone: FixedOffset) -> Option<(Expense, DuplicateCheck)> {
+    let cell = |column: Option<usize>| column.and_then(|column| row.get(column)).map(String::as_str);
+
+    let (date, precision) = parse_import_date(cell(pending_import.date_column)?, timezone)?;
+    let amount = parse_import_amount(cell(pending_import.amount_column)?)?;
+    let description = cell(pending_import.description_column)?.to_string();
+    let category = normalize_category(cell(pending_import.category_column).unwrap_or_default());
+    let category = if category.is_empty() { DEFAULT_OTHER_CATEGORY.to_string() } else { category };
+
+    let expense = Expense {
+        description,
+        amount,
+        category,
+        date,
+        tags: Vec::new(),
+        note: None,
+        attachment: None,
+        message_ids: Vec::new(),
+        account: None,
+        author: None,
+        split: Vec::new(),
+    };
+    Some((expense, DuplicateCheck::SameTime(precision)))
+}
+
+pub fn parse_import_date(value: &str, timezone: FixedOffset) -> Option<(DateTime<Utc>, Duration)> {
+    for format in DATE_TIME_FORMATS {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            let precision = if format.ends_with("%S") { Duration::seconds(1) } else { Duration::minutes(1) };
+            return Some((timezone.from_local_datetime(&date).single()?.with_timezone(&Utc), precision));
+        }
+    }
+    for format in DATE_FORMATS {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return Some((start_of_day(date, timezone), Duration::days(1)));
+        }
+    }
+    None
+}
+
+// Расходы со знаком минус берём по модулю, поступления отсеиваются раньше
+fn parse_import_amount(value: &str) -> Option<f64> {
+    parse_signed_amount(value).map(f64::abs).filter(|amount| *amount > 0.0)
+}
+
+// Банки и таблицы пишут суммы по-разному: "1 234,50", "-250.00", "1234 ₽"
+pub fn parse_signed_amount(value: &str) -> Option<f64> {
+    let value: String = value.chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, ',' | '.' | '-'))
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect();
+    // Слишком длинное число разбирается как бесконечность, а её не сохранить в JSON
+    value.parse::<f64>().ok().filter(|amount| amount.is_finite())
+}
@@ -0,0 +1,203 @@
+use crate::*;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const PERSONAL_LEDGER_ARGS: [&str; 2] = ["личный", "personal"];
+const UNKNOWN_MEMBER: &str = "Без автора";
+
+// Бюджет, с которым работает чат: групповой чат ведёт свой, личный чат - свой или выбранный общий
+pub fn ledger_id(user_data: &HashMap<ChatId, UserData>, chat_id: ChatId) -> ChatId {
+    // Ссылка действует, пока владелец личного чата остаётся участником бюджета
+    let is_member = |ledger_id: &ChatId| user_data.get(ledger_id)
+        .is_some_and(|ledger| ledger.members.iter().any(|member| ChatId::from(member.id) == chat_id));
+
+    user_data.get(&chat_id)
+        .and_then(|user_entry| user_entry.linked_ledger)
+        .filter(is_member)
+        .unwrap_or(chat_id)
+}
+
+pub fn callback_chat_id(q: &CallbackQuery) -> ChatId {
+    q.message.as_ref().map(|message| message.chat().id).unwrap_or_else(|| ChatId::from(q.from.id))
+}
+
+// В группе у каждого участника свой диалог, иначе один мог бы ответить на вопрос бота другому
+pub fn dialogue_key(update: &Update) -> Option<ChatId> {
+    let chat = update.chat()?;
+    if chat.is_private() {
+        return Some(chat.id);
+    }
+
+    let user = update.from()?;
+    let mut hasher = DefaultHasher::new();
+    (chat.id, user.id).hash(&mut hasher);
+    Some(ChatId(hasher.finish() as i64))
+}
+
+// Запоминает участников группового бюджета, чтобы показывать их имена в отчётах
+pub async fn remember_member(msg: Message, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>) {
+    let Some(user) = msg.from.as_ref().filter(|user| !user.is_bot) else {
+        return;
+    };
+    if msg.chat.is_private() {
+        return;
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    // Удалённый участник, который продолжает писать в группу, обратно не добавляется
+    if user_entry.removed.contains(&user.id) {
+        return;
+    }
+    let title = msg.chat.title().map(str::to_owned);
+    let name = user.full_name();
+
+    let has_owner = user_entry.members.iter().any(|member| member.role == Role::Owner);
+    let known = user_entry.members.iter().any(|member| member.id == user.id && member.name == name);
+    if known && has_owner && user_entry.title == title {
+        return;
+    }
+
+    info!("Updating ledger {} member {}", msg.chat.id, user.id);
+    user_entry.title = title;
+    match user_entry.members.iter_mut().find(|member| member.id == user.id) {
+        Some(member) => member.name = name,
+        None => user_entry.members.push(LedgerMember { id: user.id, name, role: Role::Editor }),
+    }
+    // Первый, кто пишет в бюджет без владельца (новый чат или бюджет до появления ролей), становится владельцем
+    if !has_owner {
+        if let Some(member) = user_entry.members.iter_mut().find(|member| member.id == user.id) {
+            member.role = Role::Owner;
+        }
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+}
+
+// В группе пишут не только траты: разбираем только ответы боту и сообщения с его упоминанием
+pub fn is_addressed_to_bot(msg: Message, me: Me) -> bool {
+    msg.chat.is_private()
+        || msg.reply_to_message().and_then(|reply| reply.from.as_ref()).is_some_and(|user| user.id == me.id)
+        || msg.text().or(msg.caption()).is_some_and(|text| text.split_whitespace().any(|word| is_bot_mention(word, &me)))
+}
+
+// Убирает из текста упоминание бота, чтобы оно не попало в описание траты
+pub fn strip_bot_mention(text: &str, me: &Me) -> String {
+    text.split_whitespace()
+        .filter(|word| !is_bot_mention(word, me))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn is_bot_mention(word: &str, me: &Me) -> bool {
+    word.strip_prefix('@').is_some_and(|username| username.eq_ignore_ascii_case(me.username()))
+}
+
+pub async fn handle_ledger_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /ledger");
+    let mut data = user_data.lock().await;
+
+    if !msg.chat.is_private() {
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
+        let mut message = String::from("Это общий бюджет чата. Участники:\n\n");
+        for (i, member) in user_entry.members.iter().enumerate() {
+            message.push_str(&format!("{}. {} ({})\n", i, member.name, role_name(member.role)));
+        }
+        message.push_str("\nНомера участников нужны для /split. Траты в группе добавляются командой /addexpense, ответом на сообщение бота или с упоминанием бота. Вести бюджет можно и из личного чата с ботом: /ledger");
+        bot.send_message(msg.chat.id, message).await?;
+        return Ok(());
+    }
+
+    let user_id = msg.from.as_ref().unwrap().id;
+    let ledgers = shared_ledgers(&data, user_id);
+    let args = args.trim();
+
+    let message = if args.is_empty() {
+        if ledgers.is_empty() {
+            String::from("Вы не участвуете в общих бюджетах. Добавьте бота в групповой чат и запишите там трату")
+        } else {
+            let current = ledger_id(&data, msg.chat.id);
+            let mut message = String::from("Ваши общие бюджеты:\n\n");
+            for (i, (id, title)) in ledgers.iter().enumerate() {
+                let marker = if *id == current { " (выбран)" } else { "" };
+                message.push_str(&format!("{}. {}{}\n", i, title, marker));
+            }
+            message.push_str("\nВыбрать: /ledger номер, вернуться к личному: /ledger личный");
+            message
+        }
+    } else if PERSONAL_LEDGER_ARGS.contains(&args.to_lowercase().as_str()) {
+        data.entry(msg.chat.id).or_default().linked_ledger = None;
+        String::from("Теперь вы работаете с личным бюджетом")
+    } else {
+        let Some((id, title)) = args.parse::<usize>().ok().and_then(|i| ledgers.get(i)) else {
+            bot.send_message(msg.chat.id, "Нет общего бюджета с таким номером, список: /ledger").await?;
+            return Ok(());
+        };
+        data.entry(msg.chat.id).or_default().linked_ledger = Some(*id);
+        format!("Теперь траты из этого чата записываются в общий бюджет '{}'", title)
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_expenses_by_member(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let mut totals: Vec<(Option<UserId>, f64)> = Vec::new();
+    for expense in user_entry.expenses.iter().filter(|e| filter.matches(e)) {
+        match totals.iter_mut().find(|(author, _)| *author == expense.author) {
+            Some((_, total)) => *total += expense.amount,
+            None => totals.push((expense.author, expense.amount)),
+        }
+    }
+
+    if totals.is_empty() {
+        bot.send_message(msg.chat.id, format!("Нет трат{}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    totals.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    let mut message = format!("Траты по участникам{}:\n\n", filter.describe());
+    for (author, total) in totals {
+        message.push_str(&format!("{}: {:.2}\n", member_name(user_entry, author), total));
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub fn member_name(user_entry: &UserData, id: Option<UserId>) -> String {
+    id.and_then(|id| user_entry.members.iter().find(|member| member.id == id))
+        .map(|member| member.name.clone())
+        .unwrap_or_else(|| UNKNOWN_MEMBER.to_string())
+}
+
+// Групповые бюджеты, в которых пользователь уже что-то записывал или писал в чат
+fn shared_ledgers(user_data: &HashMap<ChatId, UserData>, user_id: UserId) -> Vec<(ChatId, String)> {
+    let mut ledgers: Vec<(ChatId, String)> = user_data.iter()
+        .filter(|(_, user_entry)| user_entry.members.iter().any(|member| member.id == user_id))
+        .map(|(id, user_entry)| (*id, user_entry.title.clone().unwrap_or_else(|| id.to_string())))
+        .collect();
+    ledgers.sort_by_key(|(id, _)| id.0);
+    ledgers
+}
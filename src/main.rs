@@ -2,20 +2,54 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::sync::Arc;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::env;
+use std::net::SocketAddr;
+use std::time::Instant;
 use log::{info, warn};
-use tokio::{signal, sync::Mutex, sync::MutexGuard};
-use chrono::{DateTime, Utc};
+use tokio::{signal, sync::watch, sync::Mutex, sync::MutexGuard};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use teloxide::{
-    dispatching::{dialogue::{self, InMemStorage}, UpdateHandler},
+    dispatching::{dialogue::InMemStorage, UpdateHandler},
     prelude::*,
-    types::{KeyboardButton, KeyboardMarkup, ReplyMarkup},
+    net::Download,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, KeyboardButton, KeyboardMarkup, Me, MessageId, ReplyMarkup},
     utils::command::BotCommands,
 };
 use serde::{Serialize, Deserialize};
-use env_logger;
+use url::Url;
 
 use bot_structure::*;
+use categories::*;
+use config::*;
+use receipt_qr::*;
+use settings::*;
+use period::*;
+use add_income::*;
+use accounts::*;
+use recurring::*;
+use scheduler::*;
+use budgets::*;
+use goals::*;
+use reports::*;
+use digests::*;
+use reminders::*;
+use export::*;
+use import::*;
+use category_rules::*;
+use bank_import::*;
+use ofx::*;
+use qif::*;
+use bank_notifications::*;
+use ledgers::*;
+use splits::*;
+use access::*;
+use admin::*;
+use webhook::*;
+use metrics::*;
+use shutdown::*;
+use attachments::*;
+use expense_filter::*;
 use add_expenses::*;
 use add_category::*;
 use delete_category::*;
@@ -23,6 +57,36 @@ use cleanup_expenses::*;
 use show_expenses::*;
 
 pub mod bot_structure;
+pub mod categories;
+pub mod config;
+pub mod receipt_qr;
+pub mod settings;
+pub mod period;
+pub mod add_income;
+pub mod accounts;
+pub mod recurring;
+pub mod scheduler;
+pub mod budgets;
+pub mod goals;
+pub mod reports;
+pub mod digests;
+pub mod reminders;
+pub mod export;
+pub mod import;
+pub mod category_rules;
+pub mod bank_import;
+pub mod ofx;
+pub mod qif;
+pub mod bank_notifications;
+pub mod ledgers;
+pub mod splits;
+pub mod access;
+pub mod admin;
+pub mod webhook;
+pub mod metrics;
+pub mod shutdown;
+pub mod attachments;
+pub mod expense_filter;
 pub mod add_expenses;
 pub mod add_category;
 pub mod delete_category;
@@ -30,8 +94,12 @@ pub mod cleanup_expenses;
 pub mod show_expenses;
 
 const DATA_FILE_PATH: &str = "users_data.json";
+const ACCESS_FILE_PATH: &str = "access_data.json";
 const DEFAULT_OTHER_CATEGORY: &str = "Другое";
+const DEFAULT_OTHER_INCOME_CATEGORY: &str = "Прочие доходы";
 const MAX_ITEMS_IN_MESSAGE: usize = 100;
+const DEFAULT_UTC_OFFSET_MINUTES: i32 = 3 * 60;
+const DEFAULT_BUDGET_THRESHOLDS: [u32; 2] = [80, 100];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -42,39 +110,114 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let token = String::from("8148320925:AAEh0-L5Wb29tPUAYcaNsZWQ5_MN5CxsF18");
 
+    init_metrics();
+    let config = Arc::new(Config::from_env());
     let bot = Bot::new(token);
     let user_data = Arc::new(Mutex::new(load_user_data().unwrap_or_default()));
+    let access_data = Arc::new(Mutex::new(load_access_data().unwrap_or_default()));
 
-    let _dispatch_task = tokio::spawn(async move {
-        Dispatcher::builder(bot, schema())
-        .dependencies(dptree::deps![InMemStorage::<State>::new(), user_data])
-        .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
-    });
+    let (stop_scheduler, scheduler_stopped) = watch::channel(false);
+    let scheduler_task = tokio::spawn(run_scheduler(bot.clone(), user_data.clone(), scheduler_stopped));
 
-    signal::ctrl_c().await?;
+    let webhook = config.webhook.clone();
+    let metrics_address = config.metrics_address;
+    let mut dispatcher = Dispatcher::builder(bot.clone(), schema())
+        .dependencies(dptree::deps![InMemStorage::<State>::new(), user_data.clone(), config, access_data.clone()])
+        .error_handler(Arc::new(handle_dispatcher_error))
+        .build();
+    spawn_shutdown_handler(dispatcher.shutdown_token());
+
+    if let Some(address) = metrics_address {
+        start_metrics_server(address).await?;
+    }
+
+    match webhook {
+        Some(webhook) => {
+            // Без отдельного адреса /healthz и /metrics отдаёт сервер вебхука
+            let listener = start_webhook(bot, webhook, metrics_address.is_none()).await?;
+            dispatcher.dispatch_with_listener(listener, LoggingErrorHandler::with_custom_text("Webhook error")).await;
+        }
+        None => dispatcher.dispatch().await,
+    }
+
+    // Диспетчер вернёт управление только после того, как все обработчики завершатся
+    let _ = stop_scheduler.send(true);
+    if let Err(e) = scheduler_task.await {
+        warn!("Scheduler task error: {}", e);
+    }
+    flush_data(&user_data, &access_data).await;
+    info!("Bot stopped");
 
     Ok(())
 }
 
 fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let admin_command_handler = teloxide::filter_command::<AdminCommand, _>()
+        .filter(is_admin_message)
+        .branch(dptree::case![AdminCommand::Stats].endpoint(show_admin_stats))
+        .branch(dptree::case![AdminCommand::Broadcast(text)].endpoint(handle_broadcast_command))
+        .branch(dptree::case![AdminCommand::Block(args)].endpoint(handle_block_command))
+        .branch(dptree::case![AdminCommand::Unblock(args)].endpoint(handle_unblock_command))
+        .branch(dptree::case![AdminCommand::Allow(args)].endpoint(handle_allow_command))
+        .branch(dptree::case![AdminCommand::Disallow(args)].endpoint(handle_disallow_command))
+        .branch(dptree::case![AdminCommand::AdminHelp].endpoint(handle_admin_help_command));
+
     let command_handler = teloxide::filter_command::<Command, _>()
-        .branch(dptree::case![Command::Start].endpoint(handle_start_command))
+        .branch(dptree::filter_async(is_command_forbidden).endpoint(send_forbidden))
+        .branch(dptree::case![Command::Start(args)].endpoint(handle_start_command))
         .branch(dptree::case![Command::Help].endpoint(handle_help_command))
         .branch(dptree::case![Command::AddExpense].endpoint(start_add_expense))
         .branch(dptree::case![Command::AddNewCategory].endpoint(start_add_category))
         .branch(dptree::case![Command::DeleteCategory].endpoint(start_delete_category))
         .branch(dptree::case![Command::ClearAllExpenses].endpoint(start_cleanup_expenses))
-        .branch(dptree::case![Command::AllExpenses].endpoint(show_all_expenses))
-        .branch(dptree::case![Command::TotalExpenses].endpoint(show_total_expenses))
-        .branch(dptree::case![Command::ExpensesByCategory].endpoint(show_expenses_by_category))
+        .branch(dptree::case![Command::AllExpenses(args)].endpoint(show_all_expenses))
+        .branch(dptree::case![Command::TotalExpenses(args)].endpoint(show_total_expenses))
+        .branch(dptree::case![Command::ExpensesByCategory(args)].endpoint(show_expenses_by_category))
+        .branch(dptree::case![Command::ExpensesByTag(args)].endpoint(show_expenses_by_tag))
+        .branch(dptree::case![Command::Attachment(args)].endpoint(show_attachment))
+        .branch(dptree::case![Command::Timezone(args)].endpoint(handle_timezone_command))
+        .branch(dptree::case![Command::AddIncome].endpoint(start_add_income))
+        .branch(dptree::case![Command::Incomes(args)].endpoint(show_incomes))
+        .branch(dptree::case![Command::Balance(args)].endpoint(show_balance))
+        .branch(dptree::case![Command::Accounts].endpoint(show_accounts))
+        .branch(dptree::case![Command::AddAccount(args)].endpoint(handle_add_account_command))
+        .branch(dptree::case![Command::DefaultAccount(args)].endpoint(handle_default_account_command))
+        .branch(dptree::case![Command::Transfer(args)].endpoint(handle_transfer_command))
+        .branch(dptree::case![Command::Recurring].endpoint(show_recurring_expenses))
+        .branch(dptree::case![Command::AddRecurring(args)].endpoint(start_add_recurring))
+        .branch(dptree::case![Command::DeleteRecurring(args)].endpoint(handle_delete_recurring_command))
+        .branch(dptree::case![Command::Budget(args)].endpoint(handle_budget_command))
+        .branch(dptree::case![Command::Budgets].endpoint(show_budgets))
+        .branch(dptree::case![Command::BudgetAlerts(args)].endpoint(handle_budget_alerts_command))
+        .branch(dptree::case![Command::AddGoal(args)].endpoint(handle_add_goal_command))
+        .branch(dptree::case![Command::Goals].endpoint(show_goals))
+        .branch(dptree::case![Command::Contribute(args)].endpoint(handle_contribute_command))
+        .branch(dptree::case![Command::Summary(args)].endpoint(show_summary))
+        .branch(dptree::case![Command::Digest(args)].endpoint(handle_digest_command))
+        .branch(dptree::case![Command::Reminder(args)].endpoint(handle_reminder_command))
+        .branch(dptree::case![Command::Export(args)].endpoint(handle_export_command))
+        .branch(dptree::case![Command::Rule(args)].endpoint(handle_rule_command))
+        .branch(dptree::case![Command::Rules].endpoint(show_category_rules))
+        .branch(dptree::case![Command::DeleteRule(args)].endpoint(handle_delete_rule_command))
+        .branch(dptree::case![Command::Ledger(args)].endpoint(handle_ledger_command))
+        .branch(dptree::case![Command::ByMember(args)].endpoint(show_expenses_by_member))
+        .branch(dptree::case![Command::Split(args)].endpoint(handle_split_command))
+        .branch(dptree::case![Command::Settle].endpoint(show_settlements))
+        .branch(dptree::case![Command::Invite(args)].endpoint(handle_invite_command))
+        .branch(dptree::case![Command::Role(args)].endpoint(handle_role_command))
         .endpoint(handle_command);
 
     let message_handler = Update::filter_message()
+        .inspect_async(remember_member)
+        .branch(admin_command_handler)
         .branch(command_handler)
-        .branch(dptree::case![State::Default].endpoint(handle_message_expense))
+        .branch(dptree::filter_async(is_message_forbidden).endpoint(send_read_only_notice))
+        // В группах ответы и файлы принимаем, только если они адресованы боту: ответ на его сообщение или упоминание
+        .branch(dptree::filter_async(is_reply_to_expense).filter(is_addressed_to_bot).endpoint(handle_expense_reply))
+        .branch(dptree::filter(is_json_import_document).filter(is_addressed_to_bot).endpoint(handle_json_import))
+        .branch(dptree::filter(is_table_import_document).filter(is_addressed_to_bot).endpoint(handle_table_import))
+        .branch(dptree::filter(is_interchange_import_document).filter(is_addressed_to_bot).endpoint(handle_interchange_import))
+        .branch(dptree::case![State::Default].filter(is_addressed_to_bot).endpoint(handle_message_expense))
         .branch(dptree::case![State::AddExpense].endpoint(handle_message_expense))
         .branch(dptree::case![State::SelectCategory { pending_expense }].endpoint(handle_message_on_select_category))
         .branch(dptree::case![State::ConfirmAddExpense { pending_expense, category }].endpoint(handle_message_on_confirm_expense))
@@ -82,13 +225,56 @@ fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>
         .branch(dptree::case![State::ConfirmAddCategory { category }].endpoint(handle_message_on_confirm_category))
         .branch(dptree::case![State::DeleteCategory].endpoint(handle_message_on_delete_category))
         .branch(dptree::case![State::ConfirmDeleteCategory { category }].endpoint(handle_message_on_confirm_delete_category))
-        .branch(dptree::case![State::ConfirmCleanupExpenses].endpoint(handle_message_on_confirm_cleanup_expenses));
+        .branch(dptree::case![State::ConfirmCleanupExpenses].endpoint(handle_message_on_confirm_cleanup_expenses))
+        .branch(dptree::case![State::AddIncome].endpoint(handle_message_income))
+        .branch(dptree::case![State::SelectIncomeCategory { pending_income }].endpoint(handle_message_on_select_income_category))
+        .branch(dptree::case![State::ConfirmAddIncome { pending_income, category }].endpoint(handle_message_on_confirm_income))
+        .branch(dptree::case![State::AddRecurring].endpoint(handle_message_recurring))
+        .branch(dptree::case![State::SelectRecurringCategory { pending_recurring }].endpoint(handle_message_on_select_recurring_category))
+        .branch(dptree::case![State::ConfirmRecurring { pending_recurring }].endpoint(handle_message_on_confirm_recurring))
+        .branch(dptree::case![State::SelectImportColumn { pending_import, field }].endpoint(handle_message_on_select_import_column))
+        .branch(dptree::case![State::ConfirmImport { pending_import }].endpoint(handle_message_on_confirm_import))
+        .branch(dptree::case![State::ConfirmBankImport { pending_bank_import }].endpoint(handle_message_on_confirm_bank_import))
+        .branch(dptree::case![State::ConfirmImportAccounts { pending_interchange_import }].endpoint(handle_message_on_confirm_import_accounts));
+
+    let callback_query_handler = Update::filter_callback_query()
+        .branch(dptree::filter_async(is_callback_forbidden).endpoint(answer_forbidden_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, BY_CATEGORY_CALLBACK_PREFIX))
+            .endpoint(handle_expenses_by_category_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, RECURRING_CALLBACK_PREFIX))
+            .endpoint(handle_recurring_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, GOAL_CALLBACK_PREFIX))
+            .endpoint(handle_goal_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, REMINDER_CALLBACK_PREFIX))
+            .endpoint(handle_reminder_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, EXPORT_CALLBACK_PREFIX))
+            .endpoint(handle_export_callback))
+        .branch(dptree::filter(|q: CallbackQuery| is_callback_with_prefix(&q, SETTLE_CALLBACK_PREFIX))
+            .endpoint(handle_settle_callback));
+
+    // Как dialogue::enter, но в группах диалог ведётся отдельно с каждым участником
+    let dialogue_handler = dptree::filter_map(|update: Update, storage: Arc<InMemStorage<State>>| {
+        dialogue_key(&update).map(|key| MyDialogue::new(storage, key))
+    })
+    .filter_map_async(|dialogue: MyDialogue| async move {
+        match dialogue.get_or_default().await {
+            Ok(state) => Some(state),
+            Err(e) => {
+                warn!("Dialogue storage error: {}", e);
+                None
+            }
+        }
+    })
+    .inspect(record_update)
+    .branch(message_handler)
+    .branch(callback_query_handler);
 
-    dialogue::enter::<Update, InMemStorage<State>, State, _>()
-        .branch(message_handler)
+    dptree::entry()
+        .branch(dptree::filter_async(is_access_denied).endpoint(handle_access_denied))
+        .branch(dialogue_handler)
 }
 
-fn load_user_data() -> Result<HashMap<UserId, UserData>, Box<dyn Error>> {
+fn load_user_data() -> Result<HashMap<ChatId, UserData>, Box<dyn Error>> {
     info!("Loading data...");
     if !Path::new(DATA_FILE_PATH).exists() {
         info!("No data file - creating new");
@@ -97,29 +283,70 @@ fn load_user_data() -> Result<HashMap<UserId, UserData>, Box<dyn Error>> {
 
     info!("Found data file, reading...");
     let file_content = fs::read_to_string(DATA_FILE_PATH)?;
-    let user_data: HashMap<UserId, UserData> = serde_json::from_str(&file_content)?;
+    let mut user_data: serde_json::Value = serde_json::from_str(&file_content)?;
+    migrate_expense_message_ids(&mut user_data);
+    let user_data: HashMap<ChatId, UserData> = serde_json::from_value(user_data)?;
     Ok(user_data)
 }
 
-pub async fn save_user_data(user_data: &HashMap<UserId, UserData>) -> Result<(), Box<dyn Error>> {
+// Раньше у трат хранились только id сообщений. Все они были из личного чата, id которого - ключ бюджета
+fn migrate_expense_message_ids(user_data: &mut serde_json::Value) {
+    let Some(ledgers) = user_data.as_object_mut() else {
+        return;
+    };
+
+    for (chat_id, ledger) in ledgers.iter_mut() {
+        let Ok(chat_id) = chat_id.parse::<i64>() else {
+            continue;
+        };
+        let Some(expenses) = ledger.get_mut("expenses").and_then(serde_json::Value::as_array_mut) else {
+            continue;
+        };
+
+        let message_ids = expenses.iter_mut()
+            .filter_map(|expense| expense.get_mut("message_ids"))
+            .filter_map(serde_json::Value::as_array_mut)
+            .flatten();
+        for message_id in message_ids.filter(|message_id| message_id.is_object()) {
+            *message_id = serde_json::json!([chat_id, message_id.take()]);
+        }
+    }
+}
+
+pub async fn save_user_data(user_data: &HashMap<ChatId, UserData>) -> Result<(), Box<dyn Error>> {
     info!("Saving data...");
+    let started = Instant::now();
+    let result = write_user_data(user_data);
+    record_save(started.elapsed(), result.is_ok());
+    result
+}
+
+fn write_user_data(user_data: &HashMap<ChatId, UserData>) -> Result<(), Box<dyn Error>> {
     let json = serde_json::to_string_pretty(&user_data)?;
     fs::write(DATA_FILE_PATH, json)?;
     Ok(())
 }
 
-pub fn get_user_entry<'a>(user_data: &'a mut MutexGuard<'_, HashMap<UserId, UserData>>, user_id: UserId) -> &'a mut UserData {
-    let user_entry = user_data.entry(user_id).or_default();
+pub fn get_user_entry<'a>(user_data: &'a mut MutexGuard<'_, HashMap<ChatId, UserData>>, chat_id: ChatId) -> &'a mut UserData {
+    let ledger_id = ledger_id(user_data, chat_id);
+    let user_entry = user_data.entry(ledger_id).or_default();
     if user_entry.categories.is_empty() {
         user_entry.categories.push(DEFAULT_OTHER_CATEGORY.to_string());
     }
-    return user_entry;
+    if user_entry.income_categories.is_empty() {
+        user_entry.income_categories.push(DEFAULT_OTHER_INCOME_CATEGORY.to_string());
+    }
+    user_entry
+}
+
+pub fn is_callback_with_prefix(q: &CallbackQuery, prefix: &str) -> bool {
+    q.data.as_deref().is_some_and(|data| data.starts_with(prefix))
 }
 
 pub async fn enter_default_state(bot: Bot, chat_id: ChatId, dialogue: MyDialogue) -> HandlerResult {
     let markup = ReplyMarkup::kb_remove();
     bot.send_message(chat_id,
-        "Добавьте новую трату командой /addexpense, или напишите трату в формате: продукт цена (например, молоко 100)")
+        "Добавьте новую трату командой /addexpense, или напишите трату в формате: продукт цена (например, молоко 100). Доход записывается с плюсом: +зарплата 150000")
         .reply_markup(markup)
         .await?;
     dialogue.update(State::Default).await?;
@@ -143,7 +370,18 @@ async fn handle_command(
     Ok(())
 }
 
-async fn handle_start_command(bot: Bot, msg: Message, dialogue: MyDialogue) -> HandlerResult {
+async fn handle_start_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    // Ссылка-приглашение открывает бота с /start <token>
+    if !args.trim().is_empty() {
+        accept_invite(&bot, &msg, args.trim(), &user_data).await?;
+    }
+
     bot.send_message(msg.chat.id,
         "Привет! Я бот для учёта расходов. Могу запоминать ваши траты
          и выводить их в удобном для чтения виде.
@@ -154,6 +392,9 @@ async fn handle_start_command(bot: Bot, msg: Message, dialogue: MyDialogue) -> H
 }
 
 async fn handle_help_command(bot: Bot, msg: Message) -> HandlerResult {
-    bot.send_message(msg.chat.id, Command::descriptions().to_string()).await?;
+    bot.send_message(msg.chat.id, format!(
+        "{}\n\nЧтобы импортировать траты, отправьте выписку банка, таблицу CSV или Excel с заголовками, файл OFX или QIF, или JSON из /export",
+        Command::descriptions()
+    )).await?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,162 @@
+use crate::*;
+
+use std::sync::LazyLock;
+use std::time::Duration;
+use axum::{http::{header, StatusCode}, response::IntoResponse, routing::get, Router};
+use prometheus::{core::Collector, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+use teloxide::{types::UpdateKind, RequestError};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static UPDATES: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("bot_updates_total", "Handled updates by command, callback and dialogue state"),
+    &["kind", "name"]
+)));
+
+static EXPENSES_ADDED: LazyLock<IntCounterVec> = LazyLock::new(|| register(IntCounterVec::new(
+    Opts::new("bot_expenses_added_total", "Expenses added by source"),
+    &["source"]
+)));
+
+static SAVE_DURATION: LazyLock<Histogram> = LazyLock::new(|| register(Histogram::with_opts(
+    HistogramOpts::new("bot_save_duration_seconds", "Time spent writing the data file")
+)));
+
+static SAVE_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| register(IntCounter::new(
+    "bot_save_failures_total", "Failed writes of the data file"
+)));
+
+static TELEGRAM_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| register(IntCounter::new(
+    "bot_telegram_api_errors_total", "Failed Telegram API requests"
+)));
+
+// Регистрирует все метрики сразу, чтобы /metrics показывал нули, а не пустоту до первого события
+pub fn init_metrics() {
+    LazyLock::force(&UPDATES);
+    LazyLock::force(&EXPENSES_ADDED);
+    LazyLock::force(&SAVE_DURATION);
+    LazyLock::force(&SAVE_FAILURES);
+    LazyLock::force(&TELEGRAM_ERRORS);
+}
+
+fn register<T: Collector + Clone + 'static>(metric: prometheus::Result<T>) -> T {
+    let metric = metric.expect("Invalid metric definition");
+    REGISTRY.register(Box::new(metric.clone())).expect("Metric registered twice");
+    metric
+}
+
+// Откуда взялась трата: записана вручную, создана по расписанию или загружена из файла
+pub enum ExpenseSource {
+    Manual,
+    Recurring,
+    Import,
+}
+
+pub fn record_expenses_added(source: ExpenseSource, count: usize) {
+    let source = match source {
+        ExpenseSource::Manual => "manual",
+        ExpenseSource::Recurring => "recurring",
+        ExpenseSource::Import => "import",
+    };
+    EXPENSES_ADDED.with_label_values(&[source]).inc_by(count as u64);
+}
+
+pub fn record_save(duration: Duration, success: bool) {
+    SAVE_DURATION.observe(duration.as_secs_f64());
+    if !success {
+        SAVE_FAILURES.inc();
+    }
+}
+
+pub fn record_telegram_error() {
+    TELEGRAM_ERRORS.inc();
+}
+
+// Считает каждое обновление по команде, префиксу кнопки или состоянию диалога, в котором оно пришло
+pub fn record_update(update: Update, state: State, me: Me) {
+    let (kind, name) = match &update.kind {
+        UpdateKind::Message(msg) => match msg.text().and_then(|text| command_name(text, &me)) {
+            Some(command) => ("command", command),
+            None => ("state", state_name(&state).to_string()),
+        },
+        UpdateKind::CallbackQuery(q) => {
+            let prefix = q.data.as_deref().and_then(|data| data.split_once(':')).map(|(prefix, _)| prefix);
+            ("callback", prefix.unwrap_or("other").to_string())
+        }
+        _ => ("other", "other".to_string()),
+    };
+    UPDATES.with_label_values(&[kind, &name]).inc();
+}
+
+// Только известные команды, чтобы произвольный текст после '/' не плодил новые ряды метрик
+fn command_name(text: &str, me: &Me) -> Option<String> {
+    if Command::parse(text, me.username()).is_err() && AdminCommand::parse(text, me.username()).is_err() {
+        return None;
+    }
+    let command = text.split_whitespace().next()?.trim_start_matches('/');
+    Some(command.split('@').next().unwrap_or(command).to_lowercase())
+}
+
+fn state_name(state: &State) -> &'static str {
+    match state {
+        State::Default => "Default",
+        State::AddExpense => "AddExpense",
+        State::SelectCategory { .. } => "SelectCategory",
+        State::ConfirmAddExpense { .. } => "ConfirmAddExpense",
+        State::AddCategory => "AddCategory",
+        State::ConfirmAddCategory { .. } => "ConfirmAddCategory",
+        State::DeleteCategory => "DeleteCategory",
+        State::ConfirmDeleteCategory { .. } => "ConfirmDeleteCategory",
+        State::CleanupExpenses => "CleanupExpenses",
+        State::ConfirmCleanupExpenses => "ConfirmCleanupExpenses",
+        State::AddIncome => "AddIncome",
+        State::SelectIncomeCategory { .. } => "SelectIncomeCategory",
+        State::ConfirmAddIncome { .. } => "ConfirmAddIncome",
+        State::AddRecurring => "AddRecurring",
+        State::SelectRecurringCategory { .. } => "SelectRecurringCategory",
+        State::ConfirmRecurring { .. } => "ConfirmRecurring",
+        State::SelectImportColumn { .. } => "SelectImportColumn",
+        State::ConfirmImport { .. } => "ConfirmImport",
+        State::ConfirmBankImport { .. } => "ConfirmBankImport",
+        State::ConfirmImportAccounts { .. } => "ConfirmImportAccounts",
+    }
+}
+
+// Обработчик ошибок диспетчера: то же, что стандартный, но ошибки Telegram API попадают в метрики
+pub async fn handle_dispatcher_error(error: Box<dyn Error + Send + Sync>) {
+    if error.downcast_ref::<RequestError>().is_some() {
+        record_telegram_error();
+    }
+    warn!("An error has occurred in the dispatcher: {:?}", error);
+}
+
+pub fn metrics_router() -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/metrics", get(show_metrics))
+}
+
+pub async fn start_metrics_server(address: SocketAddr) -> Result<(), Box<dyn Error>> {
+    let tcp_listener = tokio::net::TcpListener::bind(address).await?;
+    info!("Metrics server listening on {}", address);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(tcp_listener, metrics_router()).await {
+            warn!("Metrics server error: {}", e);
+        }
+    });
+    Ok(())
+}
+
+async fn show_metrics() -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    let status = match encoder.encode(&REGISTRY.gather(), &mut buffer) {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            warn!("Metrics encoding error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    (status, [(header::CONTENT_TYPE, encoder.format_type().to_string())], buffer)
+}
@@ -0,0 +1,122 @@
+use crate::*;
+
+use chrono::Duration;
+
+// В OFX счёт обязателен, поэтому траты без счёта выгружаются под этим названием и при загрузке снова остаются без счёта
+const OFX_DEFAULT_ACCOUNT: &str = "Траты";
+const OFX_MAX_NAME_LEN: usize = 32;
+
+// Разбор терпим к обоим вариантам OFX: SGML (1.x) без закрывающих тегов у значений и XML (2.x)
+pub fn parse_ofx(text: &str, timezone: FixedOffset) -> Vec<ImportedTransaction> {
+    let mut transactions = Vec::new();
+    let mut account: Option<String> = None;
+    let mut fields: Option<HashMap<String, String>> = None;
+
+    for token in text.split('<').skip(1) {
+        let Some((tag, value)) = token.split_once('>') else {
+            continue;
+        };
+        let tag = tag.trim().to_uppercase();
+        let value = unescape_xml(value.trim());
+
+        match tag.as_str() {
+            "STMTTRN" => fields = Some(HashMap::new()),
+            "/STMTTRN" => {
+                if let Some(transaction) = fields.take().and_then(|fields| ofx_transaction(&fields, account.clone(), timezone)) {
+                    transactions.push(transaction);
+                }
+            }
+            "ACCTID" => account = Some(value).filter(|account| account != OFX_DEFAULT_ACCOUNT),
+            _ if !tag.starts_with('/') => {
+                if let Some(fields) = &mut fields {
+                    fields.insert(tag, value);
+                }
+            }
+            _ => {}
+        }
+    }
+    transactions
+}
+
+pub fn build_ofx(expenses: &[&Expense]) -> String {
+    let mut accounts: Vec<&str> = Vec::new();
+    for expense in expenses {
+        let account = expense.account.as_deref().unwrap_or(OFX_DEFAULT_ACCOUNT);
+        if !accounts.contains(&account) {
+            accounts.push(account);
+        }
+    }
+
+    let now = Utc::now().format("%Y%m%d%H%M%S");
+    let mut ofx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<?OFX OFXHEADER=\"200\" VERSION=\"220\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n<OFX>\n");
+    ofx.push_str(&format!("<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS><DTSERVER>{}</DTSERVER><LANGUAGE>RUS</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n", now));
+    ofx.push_str("<BANKMSGSRSV1>\n");
+
+    for account in accounts {
+        ofx.push_str("<STMTTRNRS><TRNUID>0</TRNUID><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS><STMTRS>\n");
+        ofx.push_str(&format!("<CURDEF>RUB</CURDEF><BANKACCTFROM><BANKID>0</BANKID><ACCTID>{}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n", escape_xml(account)));
+        ofx.push_str("<BANKTRANLIST>\n");
+
+        for expense in expenses.iter().filter(|expense| expense.account.as_deref().unwrap_or(OFX_DEFAULT_ACCOUNT) == account) {
+            let name: String = expense.description.chars().take(OFX_MAX_NAME_LEN).collect();
+            ofx.push_str(&format!(
+                "<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>{}[0:GMT]</DTPOSTED><TRNAMT>{:.2}</TRNAMT><FITID>{}</FITID><NAME>{}</NAME><MEMO>{}</MEMO></STMTTRN>\n",
+                expense.date.format("%Y%m%d%H%M%S"),
+                -expense.amount,
+                expense.date.timestamp_micros(),
+                escape_xml(&name),
+                escape_xml(&expense.category)
+            ));
+        }
+
+        ofx.push_str("</BANKTRANLIST>\n</STMTRS></STMTTRNRS>\n");
+    }
+
+    ofx.push_str("</BANKMSGSRSV1>\n</OFX>\n");
+    ofx
+}
+
+fn ofx_transaction(fields: &HashMap<String, String>, account: Option<String>, timezone: FixedOffset) -> Option<ImportedTransaction> {
+    let (date, precision) = parse_ofx_date(fields.get("DTPOSTED")?, timezone)?;
+    let amount = parse_signed_amount(fields.get("TRNAMT")?)?;
+    let memo = fields.get("MEMO").cloned().filter(|memo| !memo.is_empty());
+    let description = fields.get("NAME").or(fields.get("PAYEE")).cloned().filter(|name| !name.is_empty()).or(memo.clone())?;
+    let is_transfer = fields.get("TRNTYPE").is_some_and(|kind| kind.eq_ignore_ascii_case("XFER"));
+
+    Some(ImportedTransaction {
+        date,
+        // Название мы сами обрезаем при выгрузке, так что полное описание уже записанной траты с ним не совпадёт
+        check: DuplicateCheck::SameTimeShortName(precision, OFX_MAX_NAME_LEN),
+        amount,
+        description,
+        // В OFX нет категорий. Сюда их пишет наша выгрузка через MEMO, а остальное решают правила
+        category: None,
+        memo,
+        account,
+        is_transfer,
+    })
+}
+
+// Формат: 20261019120000.000[+3:MSK] или просто 20261019. Без смещения считаем время местным для пользователя
+fn parse_ofx_date(value: &str, timezone: FixedOffset) -> Option<(DateTime<Utc>, Duration)> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let offset = value.split_once('[')
+        .and_then(|(_, zone)| zone.split([':', ']']).next()?.parse::<f64>().ok())
+        .and_then(|hours| FixedOffset::east_opt((hours * 3600.0) as i32))
+        .unwrap_or(timezone);
+
+    if digits.len() >= 14 {
+        let date = NaiveDateTime::parse_from_str(&digits[..14], "%Y%m%d%H%M%S").ok()?;
+        return Some((offset.from_local_datetime(&date).single()?.with_timezone(&Utc), Duration::seconds(1)));
+    }
+    let date = NaiveDate::parse_from_str(digits.get(..8)?, "%Y%m%d").ok()?;
+    Some((start_of_day(date, offset), Duration::days(1)))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
@@ -0,0 +1,98 @@
+use crate::*;
+
+use chrono::{Datelike, Duration, Months};
+
+// Годы за этими пределами - опечатка, а на краях диапазона chrono не может прибавить месяц к дате
+const CALENDAR_YEARS: std::ops::RangeInclusive<i32> = 1970..=9999;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+    CalendarMonth { year: i32, month: u32 },
+}
+
+impl Period {
+    // Формат: сегодня, неделя, месяц, год или конкретный месяц 10.2026
+    pub fn parse(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "сегодня" | "день" => return Some(Period::Day),
+            "неделя" => return Some(Period::Week),
+            "месяц" => return Some(Period::Month),
+            "год" => return Some(Period::Year),
+            _ => {}
+        }
+
+        let (month, year) = word.split_once('.')?;
+        let month = month.parse::<u32>().ok()?;
+        let year = year.parse::<i32>().ok().filter(|year| CALENDAR_YEARS.contains(year))?;
+        NaiveDate::from_ymd_opt(year, month, 1)?;
+        Some(Period::CalendarMonth { year, month })
+    }
+
+    pub fn to_arg(&self) -> String {
+        match self {
+            Period::Day => String::from("сегодня"),
+            Period::Week => String::from("неделя"),
+            Period::Month => String::from("месяц"),
+            Period::Year => String::from("год"),
+            Period::CalendarMonth { year, month } => format!("{:02}.{}", month, year),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Period::Day => String::from("за сегодня"),
+            Period::Week => String::from("за эту неделю"),
+            Period::Month => String::from("за этот месяц"),
+            Period::Year => String::from("за этот год"),
+            Period::CalendarMonth { year, month } => format!("за {:02}.{}", month, year),
+        }
+    }
+
+    // Границы периода [начало, конец) в местном времени пользователя
+    pub fn range(&self, timezone: FixedOffset, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let today = now.with_timezone(&timezone).date_naive();
+        let (start, end) = match self {
+            Period::Day => (today, today + Duration::days(1)),
+            Period::Week => {
+                let start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                (start, start + Duration::days(7))
+            }
+            Period::Month => {
+                let start = today.with_day(1).unwrap();
+                (start, start + Months::new(1))
+            }
+            Period::Year => {
+                let start = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+                (start, start + Months::new(12))
+            }
+            Period::CalendarMonth { year, month } => {
+                let start = NaiveDate::from_ymd_opt(*year, *month, 1).unwrap();
+                (start, start + Months::new(1))
+            }
+        };
+
+        (start_of_day(start, timezone), start_of_day(end, timezone))
+    }
+
+    // Предыдущий период того же вида: вчера, прошлая неделя, прошлый месяц
+    pub fn previous_range(&self, timezone: FixedOffset, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let (start, _) = self.range(timezone, now);
+        match self {
+            Period::CalendarMonth { .. } => {
+                let previous = (start.with_timezone(&timezone) - Duration::days(1)).date_naive();
+                Period::CalendarMonth { year: previous.year(), month: previous.month() }.range(timezone, now)
+            }
+            _ => self.range(timezone, start - Duration::seconds(1)),
+        }
+    }
+}
+
+pub fn start_of_day(date: NaiveDate, timezone: FixedOffset) -> DateTime<Utc> {
+    timezone.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .unwrap()
+        .with_timezone(&Utc)
+}
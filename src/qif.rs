@@ -0,0 +1,111 @@
+use crate::*;
+
+use chrono::Duration;
+
+// QIF пишет подкатегории через ':', а класс операции после '/'
+const QIF_CATEGORY_SEPARATOR: char = ':';
+const QIF_CLASS_SEPARATOR: char = '/';
+// Сначала американский формат из спецификации, потом остальные, которые встречаются на практике.
+// %Y принимает и две цифры года, поэтому вариант с %y проверяем раньше: он требует ровно две
+const QIF_DATE_FORMATS: [&str; 7] = ["%m/%d/%y", "%m/%d/%Y", "%d/%m/%y", "%d/%m/%Y", "%d.%m.%y", "%d.%m.%Y", "%Y-%m-%d"];
+
+pub fn parse_qif(text: &str, timezone: FixedOffset) -> Vec<ImportedTransaction> {
+    let mut transactions = Vec::new();
+    let mut account: Option<String> = None;
+    let mut account_block = false;
+    let mut transaction_block = false;
+    let mut fields: HashMap<char, String> = HashMap::new();
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(header) = line.strip_prefix('!') {
+            let header = header.to_lowercase();
+            account_block = header == "account";
+            transaction_block = header.starts_with("type:")
+                && !["type:cat", "type:class", "type:memorized", "type:prices", "type:security"].contains(&header.as_str());
+            fields.clear();
+            continue;
+        }
+
+        if line != "^" {
+            let mut chars = line.chars();
+            if let Some(code) = chars.next() {
+                // Строки разбивки (S, E, $) повторяются, нам достаточно первого значения
+                fields.entry(code).or_insert_with(|| chars.as_str().trim().to_string());
+            }
+            continue;
+        }
+
+        if account_block {
+            account = fields.get(&'N').cloned();
+        } else if transaction_block {
+            if let Some(transaction) = qif_transaction(&fields, account.clone(), timezone) {
+                transactions.push(transaction);
+            }
+        }
+        fields.clear();
+    }
+    transactions
+}
+
+pub fn build_qif(expenses: &[&Expense], timezone: FixedOffset) -> String {
+    let mut by_account: Vec<(Option<&str>, Vec<&Expense>)> = Vec::new();
+    for expense in expenses {
+        let account = expense.account.as_deref();
+        match by_account.iter_mut().find(|(name, _)| *name == account) {
+            Some((_, account_expenses)) => account_expenses.push(expense),
+            None => by_account.push((account, vec![expense])),
+        }
+    }
+    // Траты без счёта идут первыми и без заголовка !Account, иначе при загрузке они попадут в предыдущий счёт
+    by_account.sort_by_key(|(account, _)| account.is_some());
+
+    let mut qif = String::new();
+    for (account, account_expenses) in by_account {
+        if let Some(account) = account {
+            qif.push_str(&format!("!Account\nN{}\nTBank\n^\n", account));
+        }
+        qif.push_str("!Type:Bank\n");
+        for expense in account_expenses {
+            qif.push_str(&format!("D{}\n", expense.date.with_timezone(&timezone).format("%m/%d/%Y")));
+            qif.push_str(&format!("T{:.2}\n", -expense.amount));
+            qif.push_str(&format!("P{}\n", expense.description));
+            qif.push_str(&format!("L{}\n", expense.category.replace(CATEGORY_SEPARATOR, &QIF_CATEGORY_SEPARATOR.to_string())));
+            if let Some(note) = &expense.note {
+                qif.push_str(&format!("M{}\n", note));
+            }
+            qif.push_str("^\n");
+        }
+    }
+    qif
+}
+
+fn qif_transaction(fields: &HashMap<char, String>, account: Option<String>, timezone: FixedOffset) -> Option<ImportedTransaction> {
+    let date = parse_qif_date(fields.get(&'D')?)?;
+    let amount = parse_signed_amount(fields.get(&'T').or(fields.get(&'U'))?)?;
+    let memo = fields.get(&'M').cloned().filter(|memo| !memo.is_empty());
+    let description = fields.get(&'P').cloned().filter(|payee| !payee.is_empty()).or(memo.clone())?;
+
+    // Категория в квадратных скобках - это перевод на другой счёт
+    let category = fields.get(&'L').map(String::as_str).unwrap_or_default();
+    let is_transfer = category.starts_with('[');
+    let category = category.split(QIF_CLASS_SEPARATOR).next().unwrap_or_default()
+        .replace(QIF_CATEGORY_SEPARATOR, &CATEGORY_SEPARATOR.to_string());
+    let category = normalize_category(&category);
+
+    Some(ImportedTransaction {
+        date: start_of_day(date, timezone),
+        check: DuplicateCheck::SameTime(Duration::days(1)),
+        amount,
+        description,
+        category: if category.is_empty() || is_transfer { None } else { Some(category) },
+        memo,
+        account,
+        is_transfer,
+    })
+}
+
+// Quicken пишет год после апострофа: 10/19'26
+fn parse_qif_date(value: &str) -> Option<NaiveDate> {
+    let value = value.replace('\'', "/").replace(' ', "");
+    QIF_DATE_FORMATS.iter().find_map(|format| NaiveDate::parse_from_str(&value, format).ok())
+}
@@ -0,0 +1,60 @@
+use crate::*;
+
+const RECEIPT_DESCRIPTION: &str = "Покупка по чеку";
+// Признак расчёта "приход", остальные (возвраты, расход) тратами не считаем
+const RECEIPT_TYPE_INCOME: &str = "1";
+
+#[derive(Debug, Clone)]
+pub struct ReceiptQr {
+    pub date: NaiveDateTime,
+    pub total: f64,
+}
+
+// Разбирает строку из QR-кода кассового чека: t=20261012T1530&s=1530.00&fn=...&i=...&fp=...&n=1
+pub fn parse_receipt_qr(text: &str) -> Option<ReceiptQr> {
+    let text = text.trim();
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let mut date = None;
+    let mut total = None;
+    let mut has_fiscal_number = false;
+    for pair in text.split('&') {
+        let (key, value) = pair.split_once('=')?;
+        match key {
+            "t" => date = parse_receipt_date(value),
+            "s" => total = value.parse::<f64>().ok().filter(|total| total.is_finite() && *total > 0.0),
+            "fn" => has_fiscal_number = !value.is_empty(),
+            "n" if value != RECEIPT_TYPE_INCOME => return None,
+            _ => {}
+        }
+    }
+
+    if !has_fiscal_number {
+        return None;
+    }
+
+    Some(ReceiptQr { date: date?, total: total? })
+}
+
+impl ReceiptQr {
+    // Время в чеке указано без часового пояса, считаем его местным временем пользователя
+    pub fn to_pending_expense(&self, timezone: FixedOffset) -> Option<PendingExpense> {
+        let date = timezone.from_local_datetime(&self.date).single()?;
+        Some(PendingExpense {
+            description: RECEIPT_DESCRIPTION.to_string(),
+            amount: self.total,
+            tags: Vec::new(),
+            message_id: None,
+            attachment: None,
+            date: Some(date.with_timezone(&Utc)),
+            account: None,
+        })
+    }
+}
+
+fn parse_receipt_date(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M").ok())
+}
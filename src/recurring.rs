@@ -0,0 +1,588 @@
+use crate::*;
+
+use chrono::Datelike;
+
+pub const RECURRING_CALLBACK_PREFIX: &str = "rec:";
+const RECURRING_ADD_ACTION: &str = "add";
+const RECURRING_SKIP_ACTION: &str = "skip";
+// Местное время, в которое списывается регулярная трата
+const RECURRING_CHARGE_HOUR: u32 = 9;
+
+pub async fn show_recurring_expenses(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.recurring_expenses.is_empty() {
+        bot.send_message(msg.chat.id,
+            "У вас нет регулярных трат. Добавьте их командой /addrecurring, например: /addrecurring аренда 30000 ежемесячно 5")
+            .await?;
+        return Ok(());
+    }
+
+    let timezone = user_entry.timezone();
+    let mut recurring_expenses: Vec<&RecurringExpense> = user_entry.recurring_expenses.iter().collect();
+    recurring_expenses.sort_by_key(|recurring| recurring.next_date);
+
+    let mut message = String::from("Ближайшие регулярные траты:\n\n");
+    for recurring in recurring_expenses.iter().take(MAX_ITEMS_IN_MESSAGE) {
+        message.push_str(&format!(
+            "{}. [{}] - **{}**: {}, на сумму: {:.2}, {}{}\n",
+            recurring.id,
+            recurring.next_date.with_timezone(&timezone).format("%d.%m.%y"),
+            recurring.category,
+            recurring.description,
+            recurring.amount,
+            recurring.schedule.describe(),
+            if recurring.confirm { ", с подтверждением" } else { "" }
+        ));
+    }
+    message.push_str("\nУдалить регулярную трату: /deleterecurring номер");
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn start_add_recurring(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /addrecurring");
+    if !args.trim().is_empty() {
+        let mut data = user_data.lock().await;
+        let user_entry = get_user_entry(&mut data, msg.chat.id);
+        handle_recurring_input(bot, msg.chat.id, &args, dialogue, user_entry).await?;
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id,
+        "Введите регулярную трату в формате: описание сумма ежемесячно день или описание сумма ежегодно дд.мм, например: аренда 30000 ежемесячно 5 или подписка 2990 ежегодно 15.03")
+        .await?;
+
+    info!("Changing state to AddRecurring");
+    dialogue.update(State::AddRecurring).await?;
+    Ok(())
+}
+
+pub async fn handle_message_recurring(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+    handle_recurring_input(bot, msg.chat.id, &text, dialogue, user_entry).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_select_recurring_category(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    mut pending_recurring: PendingRecurring,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    if text == "Назад" {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let category = match text.parse::<usize>() {
+        Ok(id) => user_entry.categories.get(id).cloned(),
+        Err(_) => Some(normalize_category(&text)).filter(|category| !category.is_empty()),
+    };
+
+    let Some(category) = category else {
+        bot.send_message(msg.chat.id, "Нет категории с таким id").await?;
+        send_select_recurring_category(bot, msg.chat.id, user_entry, dialogue, pending_recurring).await?;
+        return Ok(());
+    };
+
+    pending_recurring.category = Some(category);
+    send_confirm_recurring(bot, msg.chat.id, dialogue, pending_recurring).await?;
+    Ok(())
+}
+
+pub async fn handle_message_on_confirm_recurring(
+    bot: Bot,
+    msg: Message,
+    dialogue: MyDialogue,
+    pending_recurring: PendingRecurring,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let text = if let Some(text) = msg.text() {
+        text.to_owned()
+    } else {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    };
+
+    if text == "Отменить" {
+        enter_default_state(bot, msg.chat.id, dialogue).await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if text == "Назад" {
+        send_select_recurring_category(bot, msg.chat.id, user_entry, dialogue, pending_recurring).await?;
+        return Ok(());
+    }
+
+    let confirm = match text.as_str() {
+        "Автоматически" => false,
+        "Спрашивать" => true,
+        _ => {
+            bot.send_message(msg.chat.id, "Пожалуйста, выберите один из предложенных вариантов").await?;
+            send_confirm_recurring(bot, msg.chat.id, dialogue, pending_recurring).await?;
+            return Ok(());
+        }
+    };
+
+    let timezone = user_entry.timezone();
+    let today = Utc::now().with_timezone(&timezone).date_naive();
+    let category = pending_recurring.category.unwrap_or_else(|| DEFAULT_OTHER_CATEGORY.to_string());
+    let pending_expense = pending_recurring.pending_expense;
+    let recurring = RecurringExpense {
+        id: user_entry.next_recurring_id,
+        description: pending_expense.description,
+        amount: pending_expense.amount,
+        category: category.clone(),
+        tags: pending_expense.tags,
+        account: pending_expense.account,
+        schedule: pending_recurring.schedule,
+        next_date: charge_time(pending_recurring.schedule.next_after(today), timezone),
+        confirm,
+        awaiting_confirmation: false,
+    };
+
+    let message = format!(
+        "Регулярная трата '{}' добавлена, ближайшее списание: {}",
+        recurring.description,
+        recurring.next_date.with_timezone(&timezone).format("%d.%m.%y")
+    );
+
+    user_entry.next_recurring_id += 1;
+    user_entry.recurring_expenses.push(recurring);
+    add_category_with_parents(user_entry, &category);
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    enter_default_state(bot, msg.chat.id, dialogue).await?;
+    Ok(())
+}
+
+pub async fn handle_delete_recurring_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let Ok(id) = args.trim().parse::<u64>() else {
+        bot.send_message(msg.chat.id, "Укажите номер регулярной траты из /recurring, например: /deleterecurring 2").await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let Some(pos) = user_entry.recurring_expenses.iter().position(|recurring| recurring.id == id) else {
+        bot.send_message(msg.chat.id, "Нет регулярной траты с таким номером").await?;
+        return Ok(());
+    };
+
+    let recurring = user_entry.recurring_expenses.remove(pos);
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!("Регулярная трата '{}' удалена", recurring.description)).await?;
+    Ok(())
+}
+
+pub async fn handle_recurring_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    let data = q.data.as_deref().unwrap_or_default()[RECURRING_CALLBACK_PREFIX.len()..].to_string();
+    let Some((action, id)) = data.split_once(':') else {
+        return Ok(());
+    };
+    let Ok(id) = id.parse::<u64>() else {
+        return Ok(());
+    };
+    info!("Recurring expense callback: {} {}", action, id);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+    let timezone = user_entry.timezone();
+
+    let Some(recurring) = user_entry.recurring_expenses.iter_mut()
+        .find(|recurring| recurring.id == id && recurring.awaiting_confirmation) else {
+        if let Some(message) = q.regular_message() {
+            bot.edit_message_text(message.chat.id, message.id, "Эта регулярная трата уже обработана").await?;
+        }
+        return Ok(());
+    };
+
+    recurring.awaiting_confirmation = false;
+    let expense = (action == RECURRING_ADD_ACTION).then(|| recurring_to_expense(recurring));
+    let text = match &expense {
+        Some(_) => format!("Регулярная трата '{}' на сумму {:.2} добавлена", recurring.description, recurring.amount),
+        None => format!("Регулярная трата '{}' пропущена", recurring.description),
+    };
+    advance_recurring(recurring, timezone);
+
+    if let Some(expense) = expense {
+        add_category_with_parents(user_entry, &expense.category);
+        user_entry.expenses.push(expense);
+        record_expenses_added(ExpenseSource::Recurring, 1);
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    if let Some(message) = q.regular_message() {
+        bot.edit_message_text(message.chat.id, message.id, text).await?;
+    }
+    Ok(())
+}
+
+// Добавляет наступившие регулярные траты или спрашивает о них подтверждение
+pub async fn process_recurring_expenses(
+    bot: &Bot,
+    user_data: &Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let now = Utc::now();
+    // Для запросов подтверждения запоминаем id регулярной траты, чтобы снять ожидание, если сообщение не отправится
+    let mut notifications: Vec<(ChatId, String, Option<u64>)> = Vec::new();
+
+    let mut data = user_data.lock().await;
+    for (chat_id, user_entry) in data.iter_mut() {
+        let timezone = user_entry.timezone();
+        let mut new_expenses = Vec::new();
+
+        for recurring in &mut user_entry.recurring_expenses {
+            if recurring.confirm {
+                // На запрос так и не ответили, а уже наступило следующее списание: старое считаем пропущенным
+                if recurring.awaiting_confirmation {
+                    if following_charge(recurring, timezone) > now {
+                        continue;
+                    }
+                    info!("Recurring expense {} confirmation expired", recurring.id);
+                    while following_charge(recurring, timezone) <= now {
+                        advance_recurring(recurring, timezone);
+                    }
+                    recurring.awaiting_confirmation = false;
+                }
+
+                if recurring.next_date > now {
+                    continue;
+                }
+
+                recurring.awaiting_confirmation = true;
+                notifications.push((
+                    *chat_id,
+                    format!(
+                        "Пора оплатить регулярную трату '{}' на сумму {:.2} ({}). Добавить её?",
+                        recurring.description,
+                        recurring.amount,
+                        recurring.next_date.with_timezone(&timezone).format("%d.%m.%y")
+                    ),
+                    Some(recurring.id)
+                ));
+                continue;
+            }
+
+            // Если бот долго не работал, добавляем все пропущенные списания
+            while recurring.next_date <= now {
+                new_expenses.push(recurring_to_expense(recurring));
+                notifications.push((
+                    *chat_id,
+                    format!(
+                        "Добавлена регулярная трата '{}' на сумму {:.2} в категорию '{}'",
+                        recurring.description,
+                        recurring.amount,
+                        recurring.category
+                    ),
+                    None
+                ));
+                advance_recurring(recurring, timezone);
+            }
+        }
+
+        record_expenses_added(ExpenseSource::Recurring, new_expenses.len());
+        for expense in new_expenses {
+            add_category_with_parents(user_entry, &expense.category);
+            user_entry.expenses.push(expense);
+        }
+    }
+
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+    drop(data);
+
+    let mut unsent_confirmations = Vec::new();
+    for (chat_id, text, confirm_id) in notifications {
+        let request = bot.send_message(chat_id, text);
+        let result = match confirm_id {
+            Some(id) => request.reply_markup(recurring_confirm_keyboard(id)).await,
+            None => request.await,
+        };
+        if let Err(e) = result {
+            warn!("Failed to send recurring expense notification to {}: {}", chat_id, e);
+            record_telegram_error();
+            unsent_confirmations.extend(confirm_id.map(|id| (chat_id, id)));
+        }
+    }
+
+    // Неотправленный запрос повторится при следующем проходе планировщика
+    if !unsent_confirmations.is_empty() {
+        let mut data = user_data.lock().await;
+        for (chat_id, id) in unsent_confirmations {
+            let recurring = data.get_mut(&chat_id)
+                .and_then(|user_entry| user_entry.recurring_expenses.iter_mut().find(|recurring| recurring.id == id));
+            if let Some(recurring) = recurring {
+                recurring.awaiting_confirmation = false;
+            }
+        }
+        if let Err(e) = save_user_data(&data).await {
+            warn!("Save data error: {}", e);
+        }
+    }
+    Ok(())
+}
+
+impl RecurringSchedule {
+    pub fn describe(&self) -> String {
+        match self {
+            RecurringSchedule::Monthly { day } => format!("ежемесячно {} числа", day),
+            RecurringSchedule::Yearly { month, day } => format!("ежегодно {:02}.{:02}", day, month),
+        }
+    }
+
+    // Ближайшая дата списания строго после date. Если в месяце нет нужного дня, берётся последний день месяца
+    pub fn next_after(&self, date: NaiveDate) -> NaiveDate {
+        match *self {
+            RecurringSchedule::Monthly { day } => {
+                let this_month = clamped_date(date.year(), date.month(), day);
+                if this_month > date {
+                    return this_month;
+                }
+                let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+                clamped_date(year, month, day)
+            }
+            RecurringSchedule::Yearly { month, day } => {
+                let this_year = clamped_date(date.year(), month, day);
+                if this_year > date {
+                    return this_year;
+                }
+                clamped_date(date.year() + 1, month, day)
+            }
+        }
+    }
+}
+
+async fn handle_recurring_input(
+    bot: Bot,
+    chat_id: ChatId,
+    text: &str,
+    dialogue: MyDialogue,
+    user_entry: &mut UserData
+) -> HandlerResult {
+    let Some((expense_text, schedule)) = parse_recurring(text) else {
+        bot.send_message(chat_id,
+            "Не понимаю расписание. Укажите трату в формате: аренда 30000 ежемесячно 5 или подписка 2990 ежегодно 15.03")
+            .await?;
+        dialogue.update(State::AddRecurring).await?;
+        return Ok(());
+    };
+
+    let Some(mut pending_expense) = parse_expense(&expense_text) else {
+        bot.send_message(chat_id, "Пожалуйста, укажите трату в формате 'описание сумма', например: 'аренда 30000 ежемесячно 5'").await?;
+        dialogue.update(State::AddRecurring).await?;
+        return Ok(());
+    };
+
+    match resolve_account(user_entry, pending_expense.account.as_deref()) {
+        Ok(account) => pending_expense.account = account,
+        Err(name) => {
+            send_unknown_account(&bot, chat_id, &name).await?;
+            return Ok(());
+        }
+    }
+
+    let pending_recurring = PendingRecurring { pending_expense, schedule, category: None };
+    send_select_recurring_category(bot, chat_id, user_entry, dialogue, pending_recurring).await?;
+    Ok(())
+}
+
+async fn send_select_recurring_category(
+    bot: Bot,
+    chat_id: ChatId,
+    user_entry: &mut UserData,
+    dialogue: MyDialogue,
+    pending_recurring: PendingRecurring
+) -> HandlerResult {
+    let keyboard = KeyboardMarkup::new(
+        vec![vec![KeyboardButton::new("Назад")]])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    let mut message = String::from("Ваши категории:\n\n");
+    for (i, category) in user_entry.categories.iter().take(MAX_ITEMS_IN_MESSAGE).enumerate() {
+        message.push_str(&format!(
+            "Id: {}, название: {}\n",
+            i,
+            category));
+    }
+
+    bot.send_message(chat_id, message).await?;
+    bot.send_message(
+        chat_id,
+        format!(
+            "Регулярная трата {}, {}. Введите Id или название категории",
+            describe_pending_expense(&pending_recurring.pending_expense, user_entry.timezone()),
+            pending_recurring.schedule.describe()
+        )
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    info!("Changing state to SelectRecurringCategory");
+    dialogue.update(State::SelectRecurringCategory { pending_recurring }).await?;
+    Ok(())
+}
+
+async fn send_confirm_recurring(
+    bot: Bot,
+    chat_id: ChatId,
+    dialogue: MyDialogue,
+    pending_recurring: PendingRecurring
+) -> HandlerResult {
+    let keyboard = KeyboardMarkup::new(
+        vec![
+            vec![KeyboardButton::new("Автоматически"), KeyboardButton::new("Спрашивать")],
+            vec![KeyboardButton::new("Отменить"), KeyboardButton::new("Назад")],
+        ])
+        .resize_keyboard()
+        .one_time_keyboard();
+
+    bot.send_message(chat_id,
+        "Добавлять трату автоматически в день списания или каждый раз спрашивать подтверждение?")
+        .reply_markup(keyboard)
+        .await?;
+
+    info!("Changing state to ConfirmRecurring");
+    dialogue.update(State::ConfirmRecurring { pending_recurring }).await?;
+    Ok(())
+}
+
+// Отделяет расписание от текста траты: "аренда 30000 ежемесячно 5" -> ("аренда 30000", Monthly { day: 5 })
+fn parse_recurring(text: &str) -> Option<(String, RecurringSchedule)> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let pos = words.iter().position(|word| {
+        let word = word.to_lowercase();
+        word == "ежемесячно" || word == "ежегодно"
+    })?;
+
+    let value = words.get(pos + 1)?;
+    let schedule = if words[pos].to_lowercase() == "ежемесячно" {
+        let day = value.parse::<u32>().ok().filter(|day| (1..=31).contains(day))?;
+        RecurringSchedule::Monthly { day }
+    } else {
+        let (day, month) = value.split_once('.')?;
+        let day = day.parse::<u32>().ok()?;
+        let month = month.parse::<u32>().ok()?;
+        // Проверяем по високосному году, чтобы разрешить 29.02
+        NaiveDate::from_ymd_opt(2024, month, day)?;
+        RecurringSchedule::Yearly { month, day }
+    };
+
+    let expense_words: Vec<&str> = words[..pos].iter().chain(&words[pos + 2..]).copied().collect();
+    Some((expense_words.join(" "), schedule))
+}
+
+fn clamped_date(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day).rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap()
+}
+
+fn charge_time(date: NaiveDate, timezone: FixedOffset) -> DateTime<Utc> {
+    start_of_day(date, timezone) + chrono::Duration::hours(RECURRING_CHARGE_HOUR as i64)
+}
+
+fn advance_recurring(recurring: &mut RecurringExpense, timezone: FixedOffset) {
+    recurring.next_date = following_charge(recurring, timezone);
+}
+
+// Списание, которое идёт за ожидаемым
+fn following_charge(recurring: &RecurringExpense, timezone: FixedOffset) -> DateTime<Utc> {
+    let current = recurring.next_date.with_timezone(&timezone).date_naive();
+    charge_time(recurring.schedule.next_after(current), timezone)
+}
+
+fn recurring_confirm_keyboard(id: u64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Пропустить", recurring_callback_data(RECURRING_SKIP_ACTION, id)),
+        InlineKeyboardButton::callback("Добавить", recurring_callback_data(RECURRING_ADD_ACTION, id)),
+    ]])
+}
+
+fn recurring_to_expense(recurring: &RecurringExpense) -> Expense {
+    Expense {
+        description: recurring.description.clone(),
+        amount: recurring.amount,
+        category: recurring.category.clone(),
+        date: recurring.next_date,
+        tags: recurring.tags.clone(),
+        note: None,
+        attachment: None,
+        message_ids: Vec::new(),
+        account: recurring.account.clone(),
+        author: None,
+        split: Vec::new(),
+    }
+}
+
+fn recurring_callback_data(action: &str, id: u64) -> String {
+    format!("{}{}:{}", RECURRING_CALLBACK_PREFIX, action, id)
+}
@@ -0,0 +1,156 @@
+use crate::*;
+
+use chrono::Duration;
+
+pub const REMINDER_CALLBACK_PREFIX: &str = "remind:";
+const REMINDER_ACTION_SNOOZE: &str = "snooze";
+const REMINDER_ACTION_NOTHING_SPENT: &str = "none";
+const REMINDER_SNOOZE_HOURS: i64 = 1;
+
+pub async fn handle_reminder_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /reminder");
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let args = args.trim();
+    let message = if args.is_empty() {
+        match &user_entry.reminder {
+            Some(reminder) => format!("Напоминание приходит в {}, если за день нет трат. Отключить: /reminder выкл",
+                reminder.time.format("%H:%M")),
+            None => String::from("Напоминания отключены. Включить: /reminder 21:00"),
+        }
+    } else if ["выкл", "off"].contains(&args.to_lowercase().as_str()) {
+        user_entry.reminder = None;
+        String::from("Напоминания отключены")
+    } else {
+        let Ok(time) = NaiveTime::parse_from_str(args, "%H:%M") else {
+            bot.send_message(msg.chat.id, "Укажите время в формате ЧЧ:ММ, например: /reminder 21:00").await?;
+            return Ok(());
+        };
+
+        user_entry.reminder = Some(ReminderSettings { time, last_sent: None, snoozed_until: None, nothing_spent: None });
+        format!("Буду напоминать в {}, если за день не записано ни одной траты", time.format("%H:%M"))
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn handle_reminder_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    let action = q.data.as_deref().unwrap_or_default()[REMINDER_CALLBACK_PREFIX.len()..].to_string();
+    info!("Reminder callback: {}", action);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+    let today = Utc::now().with_timezone(&user_entry.timezone()).date_naive();
+    let Some(reminder) = &mut user_entry.reminder else {
+        return Ok(());
+    };
+
+    let text = match action.as_str() {
+        REMINDER_ACTION_SNOOZE => {
+            reminder.snoozed_until = Some(Utc::now() + Duration::hours(REMINDER_SNOOZE_HOURS));
+            format!("Хорошо, напомню через {} ч.", REMINDER_SNOOZE_HOURS)
+        }
+        REMINDER_ACTION_NOTHING_SPENT => {
+            reminder.nothing_spent = Some(today);
+            reminder.snoozed_until = None;
+            String::from("Отлично, сегодня больше не напоминаю")
+        }
+        _ => return Ok(()),
+    };
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    if let Some(message) = q.regular_message() {
+        bot.edit_message_text(message.chat.id, message.id, text).await?;
+    }
+    Ok(())
+}
+
+// Напоминает пользователям, которые сегодня ещё не записали ни одной траты
+pub async fn process_reminders(
+    bot: &Bot,
+    user_data: &Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let now = Utc::now();
+    let mut reminders: Vec<ChatId> = Vec::new();
+
+    let mut data = user_data.lock().await;
+    for (chat_id, user_entry) in data.iter_mut() {
+        let timezone = user_entry.timezone();
+        let local_now = now.with_timezone(&timezone);
+        let today = local_now.date_naive();
+        let (start, end) = Period::Day.range(timezone, now);
+        let logged_today = user_entry.expenses.iter().any(|expense| start <= expense.date && expense.date < end);
+
+        let Some(reminder) = &mut user_entry.reminder else {
+            continue;
+        };
+        if logged_today || reminder.nothing_spent == Some(today) {
+            continue;
+        }
+
+        let due = match reminder.snoozed_until {
+            Some(snoozed_until) => now >= snoozed_until,
+            None => reminder.last_sent != Some(today) && local_now.time() >= reminder.time,
+        };
+        if !due {
+            continue;
+        }
+
+        reminder.last_sent = Some(today);
+        reminder.snoozed_until = None;
+        reminders.push(*chat_id);
+    }
+
+    if reminders.is_empty() {
+        return Ok(());
+    }
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+    drop(data);
+
+    for chat_id in reminders {
+        if let Err(e) = bot.send_message(chat_id, "Сегодня вы ещё не записали ни одной траты. Просто отправьте её сообщением, например: молоко 100")
+            .reply_markup(reminder_keyboard())
+            .await
+        {
+            warn!("Failed to send reminder to {}: {}", chat_id, e);
+            record_telegram_error();
+        }
+    }
+    Ok(())
+}
+
+fn reminder_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            format!("Напомнить через {} ч.", REMINDER_SNOOZE_HOURS),
+            format!("{}{}", REMINDER_CALLBACK_PREFIX, REMINDER_ACTION_SNOOZE)
+        ),
+        InlineKeyboardButton::callback(
+            "Сегодня ничего не тратил",
+            format!("{}{}", REMINDER_CALLBACK_PREFIX, REMINDER_ACTION_NOTHING_SPENT)
+        ),
+    ]])
+}
@@ -0,0 +1,119 @@
+use crate::*;
+
+const SUMMARY_TOP_CATEGORIES: usize = 10;
+const SUMMARY_BIGGEST_EXPENSES: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub total: f64,
+    pub has_subcategories: bool,
+}
+
+pub fn total_amount<'a>(expenses: impl Iterator<Item = &'a Expense>) -> f64 {
+    expenses.map(|expense| expense.amount).sum()
+}
+
+// Суммы по категориям уровнем ниже parent, траты подкатегорий сворачиваются в родителя.
+// Отсортированы по убыванию суммы
+pub fn category_totals<'a>(expenses: impl Iterator<Item = &'a Expense>, parent: Option<&str>) -> Vec<CategoryTotal> {
+    let mut totals: HashMap<String, CategoryTotal> = HashMap::new();
+    for expense in expenses {
+        let Some(child) = child_category_of(&expense.category, parent) else {
+            continue;
+        };
+
+        let entry = totals.entry(child.clone()).or_insert_with(|| CategoryTotal {
+            category: child.clone(),
+            total: 0.0,
+            has_subcategories: false,
+        });
+        entry.total += expense.amount;
+        entry.has_subcategories |= expense.category != child;
+    }
+
+    let mut totals: Vec<CategoryTotal> = totals.into_values().collect();
+    totals.sort_by(|a, b| b.total.total_cmp(&a.total));
+    totals
+}
+
+// Суммы по тегам (трата с несколькими тегами учитывается в каждом) и сумма трат без тегов
+pub fn tag_totals<'a>(expenses: impl Iterator<Item = &'a Expense>) -> (Vec<(String, f64)>, f64) {
+    let mut totals: HashMap<String, f64> = HashMap::new();
+    let mut untagged_total = 0.0;
+    for expense in expenses {
+        if expense.tags.is_empty() {
+            untagged_total += expense.amount;
+        }
+        for tag in &expense.tags {
+            *totals.entry(tag.clone()).or_default() += expense.amount;
+        }
+    }
+
+    let mut totals: Vec<(String, f64)> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    (totals, untagged_total)
+}
+
+pub fn biggest_expenses<'a>(expenses: impl Iterator<Item = &'a Expense>, count: usize) -> Vec<&'a Expense> {
+    let mut expenses: Vec<&Expense> = expenses.collect();
+    expenses.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+    expenses.truncate(count);
+    expenses
+}
+
+pub fn expenses_between(user_entry: &UserData, (start, end): (DateTime<Utc>, DateTime<Utc>)) -> impl Iterator<Item = &Expense> {
+    user_entry.expenses.iter().filter(move |expense| start <= expense.date && expense.date < end)
+}
+
+// Сводка за период: итоги, сравнение с предыдущим периодом, траты по категориям и крупнейшие траты
+pub fn build_summary(user_entry: &UserData, period: Period, now: DateTime<Utc>) -> String {
+    let timezone = user_entry.timezone();
+    let range = period.range(timezone, now);
+    let previous_range = period.previous_range(timezone, now);
+
+    let total = total_amount(expenses_between(user_entry, range));
+    let previous_total = total_amount(expenses_between(user_entry, previous_range));
+    let income: f64 = user_entry.incomes.iter()
+        .filter(|income| range.0 <= income.date && income.date < range.1)
+        .map(|income| income.amount)
+        .sum();
+
+    let mut message = format!("Сводка {}:\n\nПотрачено: {:.2}\n", period.label(), total);
+    if previous_total > 0.0 {
+        let change = (total - previous_total) / previous_total * 100.0;
+        let direction = if change >= 0.0 { "больше" } else { "меньше" };
+        message.push_str(&format!(
+            "Это на {:.0}% {}, чем за предыдущий период ({:.2})\n",
+            change.abs(),
+            direction,
+            previous_total
+        ));
+    }
+    if income > 0.0 {
+        message.push_str(&format!("Доходы: {:.2}, баланс: {:+.2}\n", income, income - total));
+    }
+
+    if total == 0.0 {
+        message.push_str("\nТрат за этот период не было\n");
+        return message;
+    }
+
+    message.push_str("\nПо категориям:\n");
+    for category_total in category_totals(expenses_between(user_entry, range), None).iter().take(SUMMARY_TOP_CATEGORIES) {
+        message.push_str(&format!("{}: {:.2}\n", category_total.category, category_total.total));
+    }
+
+    message.push_str("\nКрупнейшие траты:\n");
+    for expense in biggest_expenses(expenses_between(user_entry, range), SUMMARY_BIGGEST_EXPENSES) {
+        message.push_str(&format!(
+            "[{}] {}: {:.2} ({})\n",
+            expense.date.with_timezone(&timezone).format("%d.%m"),
+            expense.description,
+            expense.amount,
+            expense.category
+        ));
+    }
+
+    message
+}
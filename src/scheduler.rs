@@ -0,0 +1,31 @@
+use crate::*;
+
+use std::time::Duration;
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+// Фоновая задача, которая раз в минуту выполняет всё, что должно происходить по расписанию
+pub async fn run_scheduler(bot: Bot, user_data: Arc<Mutex<HashMap<ChatId, UserData>>>, mut stopped: watch::Receiver<bool>) {
+    info!("Scheduler started");
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+    loop {
+        // Начатый проход доводится до конца, останавливаемся только между проходами
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stopped.changed() => break,
+        }
+
+        if let Err(e) = process_recurring_expenses(&bot, &user_data).await {
+            warn!("Recurring expenses error: {}", e);
+        }
+
+        if let Err(e) = process_digests(&bot, &user_data).await {
+            warn!("Digests error: {}", e);
+        }
+
+        if let Err(e) = process_reminders(&bot, &user_data).await {
+            warn!("Reminders error: {}", e);
+        }
+    }
+    info!("Scheduler stopped");
+}
@@ -0,0 +1,57 @@
+use crate::*;
+
+const MIN_UTC_OFFSET_HOURS: i32 = -12;
+const MAX_UTC_OFFSET_HOURS: i32 = 14;
+
+pub async fn handle_timezone_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /timezone");
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if args.trim().is_empty() {
+        bot.send_message(msg.chat.id,
+            format!("Ваш часовой пояс: UTC{}. Чтобы изменить его, введите, например: /timezone +3", user_entry.timezone()))
+            .await?;
+        return Ok(());
+    }
+
+    let Some(utc_offset_minutes) = parse_utc_offset(args.trim()) else {
+        bot.send_message(msg.chat.id, "Не понимаю часовой пояс. Укажите смещение от UTC, например: /timezone +3 или /timezone +5:30").await?;
+        return Ok(());
+    };
+
+    user_entry.utc_offset_minutes = utc_offset_minutes;
+    let timezone = user_entry.timezone();
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, format!("Часовой пояс изменён на UTC{}", timezone)).await?;
+    Ok(())
+}
+
+// Формат: +3, -5, 3, +5:30
+fn parse_utc_offset(text: &str) -> Option<i32> {
+    let text = text.strip_prefix("UTC").unwrap_or(text);
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (hours, minutes) = match text.split_once(':') {
+        Some((hours, minutes)) => (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?),
+        None => (text.parse::<i32>().ok()?, 0),
+    };
+
+    let hours = sign * hours;
+    if !(MIN_UTC_OFFSET_HOURS..=MAX_UTC_OFFSET_HOURS).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+
+    Some(hours * 60 + sign * minutes)
+}
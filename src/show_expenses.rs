@@ -1,38 +1,71 @@
 use crate::*;
 
+pub const BY_CATEGORY_CALLBACK_PREFIX: &str = "bycat:";
+pub const MAX_CALLBACK_DATA_LEN: usize = 64;
+const CALLBACK_FILTER_SEPARATOR: char = '|';
+
 pub async fn show_all_expenses(
     bot: Bot,
     msg: Message,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>,
 ) -> HandlerResult {
-    let user_id = msg.from.as_ref().unwrap().id;
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if user_entry.expenses.is_empty() {
         bot.send_message(msg.chat.id, "Вы пока не записали ни одну трату").await?;
         return Ok(());
     }
 
-    if user_entry.expenses.len() > MAX_ITEMS_IN_MESSAGE {
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let expenses: Vec<(usize, &Expense)> = user_entry.expenses.iter()
+        .enumerate()
+        .filter(|(_, expense)| filter.matches(expense))
+        .collect();
+
+    if expenses.is_empty() {
+        bot.send_message(msg.chat.id, format!("Нет трат{}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    if expenses.len() > MAX_ITEMS_IN_MESSAGE {
         bot.send_message(msg.chat.id,
-            format!("Показываем {} из {} ваших трат", MAX_ITEMS_IN_MESSAGE, user_entry.expenses.len()))
+            format!("Показываем {} из {} ваших трат", MAX_ITEMS_IN_MESSAGE, expenses.len()))
             .await?;
     }
 
-    let mut message = String::from("Ваши траты:\n\n");
+    let mut message = format!("Ваши траты{}:\n\n", filter.describe());
+    let timezone = user_entry.timezone();
 
-    for (id, expense) in user_entry.expenses.iter().enumerate().take(MAX_ITEMS_IN_MESSAGE) {
+    for (id, expense) in expenses.into_iter().take(MAX_ITEMS_IN_MESSAGE) {
         message.push_str(&format!(
-            "{}. [{}] - **{}**: {}, на сумму: {:.2}\n",
+            "{}. [{}] - **{}**: {}, на сумму: {:.2}",
             id,
-            expense.date.format("%d.%m.%y %H:%M"),
+            expense.date.with_timezone(&timezone).format("%d.%m.%y %H:%M"),
             expense.category,
             expense.description,
             expense.amount
         ));
+        if !expense.tags.is_empty() {
+            message.push_str(&format!(" {}", format_tags(&expense.tags)));
+        }
+        if let Some(account) = &expense.account {
+            message.push_str(&format!(" {}{}", ACCOUNT_PREFIX, account));
+        }
+        if expense.attachment.is_some() {
+            message.push_str(" 📎");
+        }
+        if let Some(note) = &expense.note {
+            message.push_str(&format!(" ({})", note));
+        }
+        message.push('\n');
     }
     
+    if user_entry.expenses.iter().any(|e| e.attachment.is_some()) {
+        message.push_str("\n📎 - есть вложение, посмотреть: /attachment номер\n");
+    }
+
     bot.send_message(msg.chat.id, message).await?;
     Ok(())
 }
@@ -40,53 +73,148 @@ pub async fn show_all_expenses(
 pub async fn show_total_expenses(
     bot: Bot,
     msg: Message,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
-    let user_id = msg.from.as_ref().unwrap().id;
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if user_entry.expenses.is_empty() {
         bot.send_message(msg.chat.id, "Вы пока не записали ни одну трату").await?;
         return Ok(());
     }
 
-    let total: f64 = user_entry.expenses.iter().map(|e| e.amount).sum();
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let total = total_amount(user_entry.expenses.iter().filter(|e| filter.matches(e)));
 
-    bot.send_message(msg.chat.id, format!("Общая сумма трат: {:.2}", total)).await?;
+    bot.send_message(msg.chat.id, format!("Общая сумма трат{}: {:.2}", filter.describe(), total)).await?;
     Ok(())
 }
 
 pub async fn show_expenses_by_category(
     bot: Bot,
     msg: Message,
-    user_data: Arc<Mutex<HashMap<UserId, UserData>>>
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
 ) -> HandlerResult {
-    let user_id = msg.from.as_ref().unwrap().id;
     let mut data = user_data.lock().await;
-    let user_entry = get_user_entry(&mut data, user_id);
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
 
     if user_entry.expenses.is_empty() {
         bot.send_message(msg.chat.id, "Вы пока не записали ни одну трату").await?;
         return Ok(());
     }
 
-    let mut category_totals: HashMap<String, f64> = HashMap::new();
-    for expense in &user_entry.expenses {
-        *category_totals.entry(expense.category.clone()).or_default() += expense.amount;
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let (message, keyboard) = build_category_report(user_entry, None, &filter);
+    bot.send_message(msg.chat.id, message).reply_markup(keyboard).await?;
+    Ok(())
+}
+
+pub async fn show_expenses_by_tag(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    if user_entry.expenses.is_empty() {
+        bot.send_message(msg.chat.id, "Вы пока не записали ни одну трату").await?;
+        return Ok(());
     }
 
-    if user_entry.categories.len() > MAX_ITEMS_IN_MESSAGE {
+    let filter = ExpenseFilter::parse(&args, user_entry.timezone());
+    let (tag_totals, untagged_total) = tag_totals(user_entry.expenses.iter().filter(|e| filter.matches(e)));
+
+    if tag_totals.len() > MAX_ITEMS_IN_MESSAGE {
         bot.send_message(msg.chat.id,
-            format!("Показываем {} из {} ваших категорий", MAX_ITEMS_IN_MESSAGE, user_entry.categories.len()))
+            format!("Показываем {} из {} ваших тегов", MAX_ITEMS_IN_MESSAGE, tag_totals.len()))
             .await?;
     }
 
-    let mut message = String::from("Траты по категориям: \n\n");
-    for (category, total) in category_totals.iter().take(MAX_ITEMS_IN_MESSAGE) {
-        message.push_str(&format!("{}: {:.2}\n", category, total));
+    let mut message = format!("Траты по тегам{}: \n\n", filter.describe());
+    for (tag, total) in tag_totals.iter().take(MAX_ITEMS_IN_MESSAGE) {
+        message.push_str(&format!("{}{}: {:.2}\n", TAG_PREFIX, tag, total));
+    }
+    if untagged_total > 0.0 {
+        message.push_str(&format!("Без тегов: {:.2}\n", untagged_total));
     }
 
     bot.send_message(msg.chat.id, message).await?;
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn handle_expenses_by_category_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    bot.answer_callback_query(q.id.clone()).await?;
+
+    let Some(message) = q.message.as_ref() else {
+        info!("Callback message is inaccessible");
+        return Ok(());
+    };
+
+    let data = q.data.as_deref().unwrap_or_default()[BY_CATEGORY_CALLBACK_PREFIX.len()..].to_string();
+    let (filter_args, parent) = data.split_once(CALLBACK_FILTER_SEPARATOR).unwrap_or(("", &data));
+    info!("Drill down expenses by category: '{}', filter: '{}'", parent, filter_args);
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+    let filter = ExpenseFilter::parse(filter_args, user_entry.timezone());
+    let parent = if parent.is_empty() { None } else { Some(parent) };
+    let (text, keyboard) = build_category_report(user_entry, parent, &filter);
+
+    bot.edit_message_text(message.chat().id, message.id(), text)
+        .reply_markup(keyboard)
+        .await?;
+    Ok(())
+}
+
+fn build_category_report(user_entry: &UserData, parent: Option<&str>, filter: &ExpenseFilter) -> (String, InlineKeyboardMarkup) {
+    let category_totals = category_totals(user_entry.expenses.iter().filter(|e| filter.matches(e)), parent);
+
+    let mut message = match parent {
+        Some(parent) => {
+            let total: f64 = category_totals.iter().map(|category_total| category_total.total).sum();
+            format!("Траты в категории '{}'{}: {:.2}\n\n", parent, filter.describe(), total)
+        }
+        None => format!("Траты по категориям{}: \n\n", filter.describe()),
+    };
+
+    if category_totals.len() > MAX_ITEMS_IN_MESSAGE {
+        message.push_str(&format!("Показываем {} из {} категорий\n\n", MAX_ITEMS_IN_MESSAGE, category_totals.len()));
+    }
+
+    let mut buttons = Vec::new();
+    for CategoryTotal { category, total, has_subcategories } in category_totals.iter().take(MAX_ITEMS_IN_MESSAGE) {
+        if Some(category.as_str()) == parent {
+            message.push_str(&format!("{} (без подкатегории): {:.2}\n", category_name(category), total));
+            continue;
+        }
+
+        let has_subcategories = *has_subcategories;
+        let marker = if has_subcategories { " ▸" } else { "" };
+        message.push_str(&format!("{}{}: {:.2}\n", category_name(category), marker, total));
+
+        let callback_data = by_category_callback_data(category, filter);
+        if has_subcategories && callback_data.len() <= MAX_CALLBACK_DATA_LEN {
+            buttons.push(vec![InlineKeyboardButton::callback(format!("{} ▸", category_name(category)), callback_data)]);
+        }
+    }
+
+    if let Some(parent) = parent {
+        let back_data = by_category_callback_data(parent_category(parent).unwrap_or_default(), filter);
+        buttons.push(vec![InlineKeyboardButton::callback("« Назад", back_data)]);
+    }
+
+    (message, InlineKeyboardMarkup::new(buttons))
+}
+
+// Фильтр идёт первым: в тегах не бывает разделителя, а в названии категории он возможен
+fn by_category_callback_data(category: &str, filter: &ExpenseFilter) -> String {
+    format!("{}{}{}{}", BY_CATEGORY_CALLBACK_PREFIX, filter.to_args(), CALLBACK_FILTER_SEPARATOR, category)
+}
@@ -0,0 +1,73 @@
+use crate::*;
+
+use std::time::Duration;
+use teloxide::dispatching::ShutdownToken;
+
+// Диспетчер мог ещё не запуститься к моменту сигнала, тогда повторяем остановку чуть позже
+const SHUTDOWN_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+// Ждёт Ctrl+C или SIGTERM от systemd и останавливает диспетчер: новые обновления больше не принимаются,
+// а уже полученные обрабатываются до конца
+pub fn spawn_shutdown_handler(shutdown_token: ShutdownToken) {
+    tokio::spawn(async move {
+        wait_for_shutdown_signal().await;
+        info!("Shutdown signal received, waiting for handlers to finish...");
+
+        loop {
+            match shutdown_token.shutdown() {
+                Ok(shutdown) => {
+                    shutdown.await;
+                    info!("Dispatcher stopped");
+                    return;
+                }
+                Err(_) => tokio::time::sleep(SHUTDOWN_RETRY_INTERVAL).await,
+            }
+        }
+    });
+}
+
+// Последнее сохранение после остановки диспетчера и планировщика, когда данные уже никто не меняет
+pub async fn flush_data(user_data: &Mutex<HashMap<ChatId, UserData>>, access_data: &Mutex<AccessData>) {
+    info!("Flushing data before exit...");
+    let data = user_data.lock().await;
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    let access_data = access_data.lock().await;
+    if let Err(e) = save_access_data(&access_data).await {
+        warn!("Save access data error: {}", e);
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {}", e);
+            wait_for_ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = wait_for_ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown_signal() {
+    wait_for_ctrl_c().await;
+}
+
+async fn wait_for_ctrl_c() {
+    if let Err(e) = signal::ctrl_c().await {
+        warn!("Failed to listen for Ctrl+C: {}", e);
+        // Без обработчика сигнала остановить бота можно только извне, поэтому просто ждём
+        std::future::pending::<()>().await;
+    }
+}
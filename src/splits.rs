@@ -0,0 +1,259 @@
+use crate::*;
+
+pub const SETTLE_CALLBACK_PREFIX: &str = "settle:";
+// Расхождения меньше копейки считаем ошибкой округления
+const MONEY_EPSILON: f64 = 0.005;
+const SHARES_SEPARATOR: char = ':';
+const EXACT_SEPARATOR: char = '=';
+
+pub async fn handle_split_command(
+    bot: Bot,
+    msg: Message,
+    args: String,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    info!("Got command /split");
+    let mut words = args.split_whitespace();
+    let Some(id) = words.next().and_then(|word| word.parse::<usize>().ok()) else {
+        bot.send_message(msg.chat.id,
+            "Укажите номер траты из /allexpenses и, при желании, участников из /ledger, например: /split 12 или /split 12 0:2 1:1")
+            .await?;
+        return Ok(());
+    };
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let Some(expense) = user_entry.expenses.get(id) else {
+        bot.send_message(msg.chat.id, "Нет траты с таким номером").await?;
+        return Ok(());
+    };
+    if expense.author.is_none() {
+        bot.send_message(msg.chat.id, "Неизвестно, кто оплатил эту трату, поэтому её нельзя разделить").await?;
+        return Ok(());
+    }
+
+    let split = match parse_split(&words.collect::<Vec<&str>>(), &user_entry.members, expense.amount) {
+        Ok(split) => split,
+        Err(error) => {
+            bot.send_message(msg.chat.id, error).await?;
+            return Ok(());
+        }
+    };
+
+    let mut message = format!("Трата '{}' на {:.2} разделена:\n", expense.description, expense.amount);
+    for share in &split {
+        message.push_str(&format!("{}: {:.2}\n", member_name(user_entry, Some(share.member)), share.amount));
+    }
+    message.push_str("\nКто кому должен: /settle");
+    user_entry.expenses[id].split = split;
+
+    if let Err(e) = save_user_data(&data).await {
+        warn!("Save data error: {}", e);
+    }
+
+    bot.send_message(msg.chat.id, message).await?;
+    Ok(())
+}
+
+pub async fn show_settlements(
+    bot: Bot,
+    msg: Message,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, msg.chat.id);
+
+    let (text, keyboard) = build_settlements_report(user_entry);
+    bot.send_message(msg.chat.id, text).reply_markup(keyboard).await?;
+    Ok(())
+}
+
+pub async fn handle_settle_callback(
+    bot: Bot,
+    q: CallbackQuery,
+    user_data: Arc<Mutex<HashMap<ChatId, UserData>>>
+) -> HandlerResult {
+    // Формат: settle:<кто платит>:<кому>:<сумма в копейках>
+    let data = q.data.as_deref().unwrap_or_default()[SETTLE_CALLBACK_PREFIX.len()..].to_string();
+    let parts: Option<Vec<u64>> = data.split(':').map(|part| part.parse::<u64>().ok()).collect();
+    let Some(&[from, to, cents]) = parts.as_deref() else {
+        bot.answer_callback_query(q.id.clone()).await?;
+        return Ok(());
+    };
+    let (from, to) = (UserId(from), UserId(to));
+    info!("Settle callback: {} pays {} to {}", from, cents, to);
+
+    // Получение денег подтверждает тот, кому их вернули, иначе должник мог бы сам списать себе долг
+    if q.from.id != to {
+        bot.answer_callback_query(q.id.clone()).text("Подтвердить перевод может только получатель").await?;
+        return Ok(());
+    }
+
+    let mut data = user_data.lock().await;
+    let user_entry = get_user_entry(&mut data, callback_chat_id(&q));
+
+    // Кнопка могла остаться в старом сообщении или её нажали дважды: сверяемся с текущими долгами
+    let debt = settlement_transfers(&member_balances(user_entry)).into_iter()
+        .find(|(debtor, creditor, _)| *debtor == from && *creditor == to)
+        .map(|(_, _, amount)| amount);
+    let answer = match debt {
+        Some(debt) => {
+            user_entry.settlements.push(Settlement {
+                from,
+                to,
+                amount: (cents as f64 / 100.0).min(debt),
+                date: Utc::now(),
+            });
+            if let Err(e) = save_user_data(&data).await {
+                warn!("Save data error: {}", e);
+            }
+            None
+        }
+        None => Some("Этот долг уже погашен"),
+    };
+
+    let mut request = bot.answer_callback_query(q.id.clone());
+    if let Some(answer) = answer {
+        request = request.text(answer);
+    }
+    request.await?;
+
+    let (text, keyboard) = build_settlements_report(get_user_entry(&mut data, callback_chat_id(&q)));
+    // При повторном нажатии отчёт уже актуален, а Telegram не даёт изменить сообщение на тот же текст
+    if let Some(message) = q.regular_message().filter(|message| message.text() != Some(text.as_str())) {
+        bot.edit_message_text(message.chat.id, message.id, text).reply_markup(keyboard).await?;
+    }
+    Ok(())
+}
+
+// Сколько каждому участнику должны остальные: плюс - ему должны, минус - должен он
+pub fn member_balances(user_entry: &UserData) -> Vec<(UserId, f64)> {
+    let mut balances: Vec<(UserId, f64)> = Vec::new();
+    let mut add = |member: UserId, amount: f64| match balances.iter_mut().find(|(id, _)| *id == member) {
+        Some((_, balance)) => *balance += amount,
+        None => balances.push((member, amount)),
+    };
+
+    for expense in &user_entry.expenses {
+        let Some(payer) = expense.author else {
+            continue;
+        };
+        for share in expense.split.iter().filter(|share| share.member != payer) {
+            add(payer, share.amount);
+            add(share.member, -share.amount);
+        }
+    }
+    for settlement in &user_entry.settlements {
+        add(settlement.from, settlement.amount);
+        add(settlement.to, -settlement.amount);
+    }
+    balances
+}
+
+// Жадно сводим самого крупного должника с самым крупным кредитором, как в Splitwise.
+// Переводов получается не больше, чем участников минус один
+pub fn settlement_transfers(balances: &[(UserId, f64)]) -> Vec<(UserId, UserId, f64)> {
+    let mut debtors: Vec<(UserId, f64)> = balances.iter().filter(|(_, b)| *b < -MONEY_EPSILON).map(|(id, b)| (*id, -b)).collect();
+    let mut creditors: Vec<(UserId, f64)> = balances.iter().filter(|(_, b)| *b > MONEY_EPSILON).copied().collect();
+
+    let mut transfers = Vec::new();
+    loop {
+        debtors.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        creditors.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        let (Some(debtor), Some(creditor)) = (debtors.first_mut(), creditors.first_mut()) else {
+            break;
+        };
+
+        let amount = debtor.1.min(creditor.1);
+        transfers.push((debtor.0, creditor.0, amount));
+        debtor.1 -= amount;
+        creditor.1 -= amount;
+        debtors.retain(|(_, b)| *b > MONEY_EPSILON);
+        creditors.retain(|(_, b)| *b > MONEY_EPSILON);
+    }
+    transfers
+}
+
+fn build_settlements_report(user_entry: &UserData) -> (String, InlineKeyboardMarkup) {
+    let balances = member_balances(user_entry);
+    let transfers = settlement_transfers(&balances);
+    if transfers.is_empty() {
+        return (String::from("Все в расчёте"), InlineKeyboardMarkup::default());
+    }
+
+    let mut message = String::from("Балансы:\n");
+    for (member, balance) in balances.iter().filter(|(_, balance)| balance.abs() > MONEY_EPSILON) {
+        message.push_str(&format!("{}: {:+.2}\n", member_name(user_entry, Some(*member)), balance));
+    }
+
+    message.push_str("\nЧтобы рассчитаться:\n");
+    let mut buttons = Vec::new();
+    for (from, to, amount) in transfers {
+        let from_name = member_name(user_entry, Some(from));
+        let to_name = member_name(user_entry, Some(to));
+        message.push_str(&format!("{} → {}: {:.2}\n", from_name, to_name, amount));
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("{} отдал {} {:.2}", from_name, to_name, amount),
+            format!("{}{}:{}:{}", SETTLE_CALLBACK_PREFIX, from, to, (amount * 100.0).round() as u64)
+        )]);
+    }
+
+    message.push_str("\nОтметить перевод может тот, кто получил деньги");
+    (message, InlineKeyboardMarkup::new(buttons))
+}
+
+// Аргументы после номера траты: пусто - поровну на всех, "0 2" - поровну на выбранных,
+// "0:2 1:1" - по долям, "0=1500 1=500" - точными суммами
+fn parse_split(args: &[&str], members: &[LedgerMember], amount: f64) -> Result<Vec<SplitShare>, String> {
+    if members.is_empty() {
+        return Err(String::from("В этом бюджете пока нет участников. Делить траты можно в групповом чате"));
+    }
+    if args.is_empty() {
+        let weights: Vec<(UserId, f64)> = members.iter().map(|member| (member.id, 1.0)).collect();
+        return Ok(split_by_weights(&weights, amount));
+    }
+
+    let member = |index: &str| index.parse::<usize>().ok()
+        .and_then(|index| members.get(index))
+        .map(|member| member.id)
+        .ok_or_else(|| format!("Нет участника с номером {}, список: /ledger", index));
+
+    if args.iter().all(|arg| arg.contains(EXACT_SEPARATOR)) {
+        let mut split = Vec::new();
+        for arg in args {
+            let (index, value) = arg.split_once(EXACT_SEPARATOR).unwrap_or_default();
+            let value = value.parse::<f64>().ok().filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("Не понимаю сумму '{}'", value))?;
+            split.push(SplitShare { member: member(index)?, amount: value });
+        }
+        let total: f64 = split.iter().map(|share| share.amount).sum();
+        if (total - amount).abs() > MONEY_EPSILON {
+            return Err(format!("Сумма долей {:.2} не совпадает с суммой траты {:.2}", total, amount));
+        }
+        return Ok(split);
+    }
+
+    let mut weights = Vec::new();
+    for arg in args {
+        let (index, weight) = arg.split_once(SHARES_SEPARATOR).unwrap_or((arg, "1"));
+        let weight = weight.parse::<f64>().ok().filter(|weight| weight.is_finite() && *weight > 0.0)
+            .ok_or_else(|| format!("Не понимаю долю '{}'", weight))?;
+        weights.push((member(index)?, weight));
+    }
+    Ok(split_by_weights(&weights, amount))
+}
+
+// Округляем до копеек, остаток от округления достаётся первому участнику
+fn split_by_weights(weights: &[(UserId, f64)], amount: f64) -> Vec<SplitShare> {
+    let total_weight: f64 = weights.iter().map(|(_, weight)| weight).sum();
+    let mut split: Vec<SplitShare> = weights.iter()
+        .map(|(member, weight)| SplitShare { member: *member, amount: (amount * weight / total_weight * 100.0).round() / 100.0 })
+        .collect();
+
+    let remainder = amount - split.iter().map(|share| share.amount).sum::<f64>();
+    if let Some(first) = split.first_mut() {
+        first.amount += remainder;
+    }
+    split
+}
@@ -0,0 +1,35 @@
+use crate::*;
+
+use std::convert::Infallible;
+use teloxide::update_listeners::{webhooks, UpdateListener};
+
+// Поднимает HTTP-сервер для вебхука и сообщает Telegram, куда присылать обновления
+pub async fn start_webhook(bot: Bot, webhook: WebhookConfig, serve_metrics: bool) -> Result<impl UpdateListener<Err = Infallible>, Box<dyn Error>> {
+    let mut options = webhooks::Options::new(webhook.address, webhook.url.clone());
+    if let Some(secret_token) = webhook.secret_token {
+        options = options.secret_token(secret_token);
+    }
+
+    if webhook.skip_setup {
+        info!("Webhook setup skipped, expecting updates at {}", webhook.address);
+    } else {
+        let secret_token = options.get_or_gen_secret_token().to_string();
+        bot.set_webhook(webhook.url.clone()).secret_token(secret_token).await?;
+        info!("Webhook set to {}", webhook.url);
+    }
+
+    let (listener, stop_flag, mut router) = webhooks::axum_no_setup(options);
+    if serve_metrics {
+        router = router.merge(metrics_router());
+    }
+    let tcp_listener = tokio::net::TcpListener::bind(webhook.address).await?;
+    info!("Webhook server listening on {}", webhook.address);
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(tcp_listener, router).with_graceful_shutdown(stop_flag).await {
+            warn!("Webhook server error: {}", e);
+        }
+    });
+
+    Ok(listener)
+}